@@ -1,22 +1,28 @@
 use anyhow::Context;
+use chrono::Datelike;
 use rusqlite::OptionalExtension;
 
-use crate::{db, tmdb::OptionalDate};
+use crate::{
+    cli::PickStrategy,
+    db,
+    tmdb::{self, OptionalDate},
+    update,
+};
 
 use super::{AppContext, EpisodeDetails, SeriesId};
 
-pub fn add_series_by_title(
+fn search_candidates(
     ctx: &mut AppContext,
     title: &str,
     first_air_year: Option<i32>,
-) -> anyhow::Result<bool> {
+) -> anyhow::Result<Vec<tmdb::SeriesFound>> {
     log::info!(
-        "Searching for series to add: {title}{}",
+        "Searching for series: {title}{}",
         first_air_year
             .map(|y| format!(" ({y})"))
             .unwrap_or_default()
     );
-    let search_result = ctx.tmdb.search_series(title, first_air_year)?;
+    let search_result = ctx.tmdb.search_series(title, first_air_year, 1)?;
 
     for sr in search_result.results.iter() {
         log::debug!(
@@ -29,38 +35,183 @@ pub fn add_series_by_title(
     }
 
     // NOTE: sometimes the search results seem to contain non-series results, perhaps episodes? those always have `first_air_date == None`, but we can't just use that as a filter because then we prevent announced yet unreleased series to be added
-    let mut candidates: Vec<_> = search_result.results;
+    Ok(search_result.results)
+}
 
-    // see if any candidates have an exact name match, if so, we want an exact match
-    // TODO: this is not foolproof if a release year is not specified, as it may be that an ancient title happens to match the exact spelling while we actually wanted one that has an extra dot or color or whatever
+/// Drops all candidates that don't match the title exactly, unless none of them do.
+fn retain_exact_matches(candidates: &mut Vec<tmdb::SeriesFound>, title: &str) {
+    // TODO: this is not foolproof if a release year is not specified, as it may be that an ancient title happens to match the exact spelling while we actually wanted one that has an extra dot or color or whatever; use --interactive in that case
     if candidates.iter().any(|sr| sr.name == title) {
         candidates.retain(|sr| sr.name == title);
     }
+}
 
-    // if there are multiple candidates, prefer the one that is already released and most recently so
-    // this is already how `Option<chrono::NaiveDate>` and so `OptionalDate` are ordered, so we can just take the max
-    let best_match = candidates.iter().max_by_key(|sr| sr.first_air_date);
+/// Picks the series we most likely mean among the search results, without asking the user.
+pub fn pick_candidate(
+    mut candidates: Vec<tmdb::SeriesFound>,
+    title: &str,
+    strategy: PickStrategy,
+) -> Option<tmdb::SeriesFound> {
+    // see if any candidates have an exact name match, if so, we want an exact match
+    retain_exact_matches(&mut candidates, title);
 
-    let best_match = match best_match {
-        None => {
+    match strategy {
+        // TMDB returns the results ordered by relevance
+        PickStrategy::First => candidates.into_iter().next(),
+        // prefer the one that is already released and most recently so
+        // this is already how `Option<chrono::NaiveDate>` and so `OptionalDate` are ordered, so we can just take the max
+        PickStrategy::Newest => candidates.into_iter().max_by_key(|sr| sr.first_air_date),
+        PickStrategy::MostPopular => candidates
+            .into_iter()
+            .max_by(|a, b| a.popularity.total_cmp(&b.popularity)),
+    }
+}
+
+/// Searches TMDB for the series with the given title that we most likely mean.
+pub fn find_best_match(
+    ctx: &mut AppContext,
+    title: &str,
+    first_air_year: Option<i32>,
+    strategy: PickStrategy,
+) -> anyhow::Result<Option<tmdb::SeriesFound>> {
+    let candidates = search_candidates(ctx, title, first_air_year)?;
+    let best_match = pick_candidate(candidates, title, strategy);
+
+    match &best_match {
+        None => log::error!("-- No results"),
+        Some(bm) => log::info!(
+            "-- Selected: {} ({}): {}",
+            bm.name,
+            bm.first_air_date,
+            bm.overview
+        ),
+    }
+
+    Ok(best_match)
+}
+
+/// Formats a search result as a line of its main details, followed by an indented line with the start of the overview.
+pub fn format_candidate(candidate: &tmdb::SeriesFound) -> String {
+    const MAX_OVERVIEW_CHARS: usize = 100;
+
+    let mut line = format!(
+        "{} ({})",
+        candidate.name,
+        candidate
+            .first_air_date
+            .map(|dt| dt.year().to_string())
+            .unwrap_or("unreleased".to_owned())
+    );
+    if !candidate.origin_country.is_empty() {
+        line += &format!(" [{}]", candidate.origin_country.join(", "));
+    }
+    line += &format!(
+        " | popularity {:.1} | TMDB ID {}",
+        candidate.popularity, candidate.id
+    );
+
+    if !candidate.overview.is_empty() {
+        let mut overview: String = candidate
+            .overview
+            .chars()
+            .take(MAX_OVERVIEW_CHARS)
+            .collect();
+        if overview.len() < candidate.overview.len() {
+            overview += "...";
+        }
+        line += &format!("\n    {overview}");
+    }
+    line
+}
+
+/// Lists the candidates and asks the user to choose one of them.
+/// Returns the index of the chosen candidate, or `None` if the user didn't choose any.
+pub fn choose_candidate(
+    candidates: &[tmdb::SeriesFound],
+    input: &mut impl std::io::BufRead,
+    output: &mut impl std::io::Write,
+) -> anyhow::Result<Option<usize>> {
+    for (idx, candidate) in candidates.iter().enumerate() {
+        writeln!(output, "{:>2}) {}", idx + 1, format_candidate(candidate))?;
+    }
+
+    loop {
+        write!(
+            output,
+            "Choose a series to add (1-{}), or nothing to cancel: ",
+            candidates.len()
+        )?;
+        output.flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Ok(None);
+        }
+
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Ok(None);
+        }
+        match answer.parse::<usize>() {
+            Ok(number) if (1..=candidates.len()).contains(&number) => return Ok(Some(number - 1)),
+            _ => writeln!(output, "Invalid choice: {answer}")?,
+        }
+    }
+}
+
+pub fn add_series_by_title(
+    ctx: &mut AppContext,
+    title: &str,
+    first_air_year: Option<i32>,
+    strategy: PickStrategy,
+    interactive: bool,
+    user: &db::User,
+) -> anyhow::Result<bool> {
+    let selected = if interactive {
+        let candidates = search_candidates(ctx, title, first_air_year)?;
+        if candidates.is_empty() {
             log::error!("-- No results");
             return Ok(false);
         }
-        Some(bm) => bm,
+
+        let choice = choose_candidate(
+            &candidates,
+            &mut std::io::stdin().lock(),
+            &mut std::io::stdout(),
+        )?;
+        match choice {
+            None => {
+                log::info!("-- Nothing selected");
+                return Ok(false);
+            }
+            Some(idx) => candidates[idx].id,
+        }
+    } else {
+        let Some(best_match) = find_best_match(ctx, title, first_air_year, strategy)? else {
+            return Ok(false);
+        };
+        best_match.id
     };
 
-    log::info!(
-        "-- Selected: {} ({}): {}",
-        best_match.name,
-        best_match.first_air_date,
-        best_match.overview
-    );
+    add_series_by_id(ctx, selected, user)?;
+    Ok(true)
+}
 
-    add_series_by_id(ctx, best_match.id)
+/// The result of adding a series for a user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddOutcome {
+    /// The user now tracks the series; it was either newly added, or already tracked by other users.
+    Added,
+    /// The user was already tracking the series.
+    AlreadyTracked,
 }
 
-pub fn add_series_by_id(ctx: &mut AppContext, id: SeriesId) -> anyhow::Result<bool> {
-    log::info!("Adding series by TMDB id: {id}");
+pub fn add_series_by_id(
+    ctx: &mut AppContext,
+    id: SeriesId,
+    user: &db::User,
+) -> anyhow::Result<AddOutcome> {
+    log::info!("Adding series by TMDB id for user {user}: {id}");
 
     let existing_series = ctx
         .db
@@ -74,24 +225,61 @@ pub fn add_series_by_id(ctx: &mut AppContext, id: SeriesId) -> anyhow::Result<bo
         .with_context(|| format!("Looking for series with ID {id}"))?;
 
     if let Some((existing_title, existing_release_date)) = existing_series {
+        if ctx
+            .db
+            .insert_tracked_series(user.id, id, chrono::Utc::now())?
+        {
+            log::info!(
+                "-- Series is already tracked, subscribed user to it: {existing_title} ({existing_release_date})"
+            );
+            return Ok(AddOutcome::Added);
+        }
         log::warn!(
-            "-- Ignoring: series is already tracked: {existing_title} ({existing_release_date})"
+            "-- Ignoring: series is already tracked by user: {existing_title} ({existing_release_date})"
         );
-        return Ok(true);
+        return Ok(AddOutcome::AlreadyTracked);
     }
 
-    let series_details = ctx.tmdb.get_series_details(id)?;
-    let series_poster = ctx.tmdb.get_poster(&series_details.poster_path)?;
+    let new_series = fetch_new_series(ctx, id)?;
+
+    // a series nobody tracks would never be updated nor removed, so it's stored along with the subscription
+    ctx.db.in_transaction(|db| {
+        let new_series = store_new_series(db, new_series)?;
+        db.insert_tracked_series(user.id, new_series.tmdb_id, new_series.update_timestamp)
+            .with_context(|| {
+                format!(
+                    "Inserting tracked series for new series: {}",
+                    new_series.details.identify()
+                )
+            })
+    })?;
+
+    Ok(AddOutcome::Added)
+}
+
+/// Everything about a series we fetch from TMDB before adding it.
+pub struct NewSeries {
+    details: tmdb::SeriesDetails,
+    poster: tmdb::Poster,
+    external_ids: tmdb::ExternalIds,
+    seasons: Vec<tmdb::SeasonDetails>,
+}
+
+/// Fetches the series with all its seasons from TMDB, without storing anything, see `store_new_series`.
+pub fn fetch_new_series(ctx: &mut AppContext, id: SeriesId) -> anyhow::Result<NewSeries> {
+    let details = ctx.tmdb.get_series_details(id)?;
+    let poster = ctx.tmdb.get_poster(&details.poster_path)?;
+    let external_ids = ctx.tmdb.get_external_ids(id)?;
 
     log::info!(
         "-- In production: {} | status: {}",
-        series_details.in_production,
-        series_details.status
+        details.in_production,
+        details.status
     );
 
     log::info!(
         "-- Last episode: {}",
-        series_details
+        details
             .last_episode_to_air
             .as_ref()
             .map(EpisodeDetails::identify)
@@ -100,14 +288,34 @@ pub fn add_series_by_id(ctx: &mut AppContext, id: SeriesId) -> anyhow::Result<bo
 
     log::info!(
         "-- Next episode: {}",
-        series_details
+        details
             .next_episode_to_air
             .as_ref()
             .map(EpisodeDetails::identify)
             .unwrap_or("unknown".to_owned())
     );
 
-    ctx.db.conn.execute(
+    let seasons = update::fetch_seasons(ctx, id, 1..=details.number_of_seasons)?;
+
+    Ok(NewSeries {
+        details,
+        poster,
+        external_ids,
+        seasons,
+    })
+}
+
+/// Stores the series along with its poster and seasons. Should be run in a transaction,
+/// so that a failure halfway through doesn't leave e.g. a poster without a series behind.
+pub fn store_new_series(db: &mut db::Db, new_series: NewSeries) -> anyhow::Result<db::Series> {
+    let NewSeries {
+        details: series_details,
+        poster: series_poster,
+        external_ids,
+        seasons,
+    } = new_series;
+
+    db.conn.execute(
         "INSERT INTO posters (img_data, mime_type, source_url) VALUES (:img_data, :mime_type, :source_url)",
         rusqlite::named_params! {
             ":img_data": series_poster.img_data,
@@ -115,10 +323,10 @@ pub fn add_series_by_id(ctx: &mut AppContext, id: SeriesId) -> anyhow::Result<bo
             ":source_url": series_poster.source_url,
         }
     ).with_context(|| format!("Inserting series {} poster from {}", series_details.identify(), series_poster.source_url))?;
-    let new_poster_id = db::PosterId(ctx.db.conn.last_insert_rowid());
+    let new_poster_id = db::PosterId(db.conn.last_insert_rowid());
 
     let new_series = db::Series {
-        tmdb_id: id,
+        tmdb_id: series_details.id,
         title: series_details.name.clone(),
         first_air_date: series_details.first_air_date,
         poster_id: new_poster_id,
@@ -126,56 +334,577 @@ pub fn add_series_by_id(ctx: &mut AppContext, id: SeriesId) -> anyhow::Result<bo
         in_production: series_details.in_production,
         last_episode_air_date: series_details.last_episode_date(),
         next_episode_air_date: series_details.next_episode_date(),
-        details: series_details.clone(),
         details_json: serde_json::to_value(&series_details).unwrap(),
+        details: series_details,
+        external_ids,
         update_timestamp: chrono::Utc::now(),
+        orphaned_timestamp: None,
     };
-    ctx.db.insert_series(&new_series)?;
+    db.insert_series(&new_series)?;
 
-    // TODO: user ID is me, but make this more flexible eventually
-    ctx.db.conn.execute(
-        "INSERT INTO tracked_series (user_id, series_tmdb_id, start_timestamp) VALUES (:user_id, :series_id, :start_timestamp)",
-        rusqlite::named_params! {
-            ":user_id": 1,
-            ":series_id": new_series.tmdb_id,
-            ":start_timestamp": new_series.update_timestamp,
-        }
-    ).with_context(|| format!("Inserting tracked series for new series: {}", series_details.identify()))?;
+    update::store_seasons(
+        db,
+        new_series.tmdb_id,
+        &seasons,
+        new_series.update_timestamp,
+    )?;
 
-    Ok(true)
+    Ok(new_series)
 }
 
-pub fn multi_add_series_from_file(
+pub fn add_series_by_external_id(
     ctx: &mut AppContext,
-    file_path: &std::path::Path,
-) -> anyhow::Result<()> {
-    log::info!("Adding all series from file: {file_path:?}");
+    source: tmdb::ExternalSource,
+    external_id: &str,
+    user: &db::User,
+) -> anyhow::Result<bool> {
+    log::info!("Looking up series by {source} ID: {external_id}");
+    let results = ctx.tmdb.find_by_external_id(source, external_id)?;
+
+    let Some(found) = results.first() else {
+        log::error!("-- No series with {source} ID {external_id}");
+        return Ok(false);
+    };
+    if results.len() > 1 {
+        log::warn!(
+            "-- {} series have {source} ID {external_id}, taking the first one",
+            results.len()
+        );
+    }
+
+    log::info!(
+        "-- Found: {} ({}): {}",
+        found.name,
+        found.first_air_date,
+        found.overview
+    );
+
+    add_series_by_id(ctx, found.id, user)?;
+    Ok(true)
+}
+
+/// A line of a file given to `multi_add_series_from_file`.
+#[derive(Debug, PartialEq)]
+enum SeriesLine<'a> {
+    Title(&'a str, Option<i32>),
+    ExternalId(tmdb::ExternalSource, &'a str),
+}
+
+fn parse_line(line: &str) -> SeriesLine<'_> {
+    let line = line.trim();
+
+    // an external ID prefixed by its source, e.g. imdb:tt13016388
+    if let Some((source, external_id)) = line.split_once(':') {
+        if let Ok(source) = source.parse::<tmdb::ExternalSource>() {
+            if !external_id.is_empty() && !external_id.contains(char::is_whitespace) {
+                return SeriesLine::ExternalId(source, external_id);
+            }
+        }
+    }
+
+    // otherwise a title, optionally ending in the release (first air) year in parens, e.g. (2024)
+    let Some((title, maybe_year)) = line.rsplit_once(' ') else {
+        return SeriesLine::Title(line, None);
+    };
+
+    if !maybe_year.starts_with('(') || !maybe_year.ends_with(')') {
+        return SeriesLine::Title(line, None);
+    }
+
+    match maybe_year[1..maybe_year.len() - 1].parse() {
+        Ok(year) => SeriesLine::Title(title.trim_end(), Some(year)),
+        Err(_) => SeriesLine::Title(line, None),
+    }
+}
+
+/// Which series an entry to be added (e.g. a line of a file given to `multi_add_series_from_file`) refers to.
+#[derive(Debug)]
+pub enum CandidateMatch {
+    Found(SeriesId),
+    NotFound,
+    Ambiguous(Vec<tmdb::SeriesFound>),
+}
+
+/// Narrows down the candidates to the one series we mean. If several series match equally well, `pick` decides
+/// which one, or, if not given, the match is ambiguous. Without a title, e.g. when looking up by external ID,
+/// the first candidate is picked.
+pub fn match_candidates(
+    mut candidates: Vec<tmdb::SeriesFound>,
+    title: Option<&str>,
+    pick: Option<PickStrategy>,
+) -> CandidateMatch {
+    if let Some(title) = title {
+        retain_exact_matches(&mut candidates, title);
+    }
 
-    // Allow the line to optionally end in the release (first air) year in parens, e.g. (2024).
-    fn parse_line(line: &str) -> (&str, Option<i32>) {
-        let Some((title, maybe_year)) = line.trim().rsplit_once(' ') else {
-            return (line.trim(), None);
+    let found = if candidates.len() > 1 {
+        let Some(strategy) = pick else {
+            return CandidateMatch::Ambiguous(candidates);
         };
+        match title {
+            Some(title) => pick_candidate(candidates, title, strategy),
+            // external IDs should be unique, there is nothing to pick by
+            None => candidates.into_iter().next(),
+        }
+    } else {
+        candidates.pop()
+    };
 
-        if !maybe_year.starts_with('(') || !maybe_year.ends_with(')') {
-            return (line.trim(), None);
+    match found {
+        None => CandidateMatch::NotFound,
+        Some(found) => {
+            log::info!(
+                "-- Selected: {} ({}): {}",
+                found.name,
+                found.first_air_date,
+                found.overview
+            );
+            CandidateMatch::Found(found.id)
         }
+    }
+}
 
-        match maybe_year[1..maybe_year.len() - 1].parse() {
-            Ok(year) => (title.trim_end(), Some(year)),
-            Err(_) => (line.trim(), None),
+/// Searches TMDB for the series with the given title, see `match_candidates`.
+pub fn match_title(
+    ctx: &mut AppContext,
+    title: &str,
+    first_air_year: Option<i32>,
+    pick: Option<PickStrategy>,
+) -> anyhow::Result<CandidateMatch> {
+    let candidates = search_candidates(ctx, title, first_air_year)?;
+    Ok(match_candidates(candidates, Some(title), pick))
+}
+
+/// Looks up the series with the given external ID on TMDB, see `match_candidates`.
+pub fn match_external_id(
+    ctx: &mut AppContext,
+    source: tmdb::ExternalSource,
+    external_id: &str,
+    pick: Option<PickStrategy>,
+) -> anyhow::Result<CandidateMatch> {
+    log::info!("Looking up series by {source} ID: {external_id}");
+    let candidates = ctx.tmdb.find_by_external_id(source, external_id)?;
+    Ok(match_candidates(candidates, None, pick))
+}
+
+/// The outcome of adding a batch of series, e.g. all series listed in a file. Lines that could not be added are kept,
+/// so that they can be fixed and retried.
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub already_tracked: usize,
+    pub not_found: Vec<String>,
+    pub ambiguous: Vec<String>,
+    /// The lines along with the error adding them.
+    pub errored: Vec<(String, String)>,
+}
+
+impl ImportSummary {
+    /// Adds the matched series for the user, and records the outcome for the line.
+    /// Errors, whether from matching or adding, are recorded rather than returned.
+    pub fn add_match(
+        &mut self,
+        ctx: &mut AppContext,
+        line: &str,
+        matched: anyhow::Result<CandidateMatch>,
+        user: &db::User,
+    ) {
+        let added = matched.and_then(|m| match m {
+            CandidateMatch::Found(id) => add_series_by_id(ctx, id, user).map(Some),
+            CandidateMatch::NotFound => {
+                log::error!("-- No results for: {line}");
+                self.not_found.push(line.to_owned());
+                Ok(None)
+            }
+            CandidateMatch::Ambiguous(candidates) => {
+                log::error!(
+                    "-- {} series match, skipping: {line}\n{}",
+                    candidates.len(),
+                    candidates
+                        .iter()
+                        .map(format_candidate)
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                );
+                self.ambiguous.push(line.to_owned());
+                Ok(None)
+            }
+        });
+
+        match added {
+            Ok(Some(AddOutcome::Added)) => self.added += 1,
+            Ok(Some(AddOutcome::AlreadyTracked)) => self.already_tracked += 1,
+            Ok(None) => {}
+            Err(err) => {
+                log::error!("-- Failed to add {line:?}: {err:?}");
+                self.errored.push((line.to_owned(), format!("{err:#}")));
+            }
+        }
+    }
+
+    pub fn unresolved_count(&self) -> usize {
+        self.not_found.len() + self.ambiguous.len() + self.errored.len()
+    }
+
+    /// Writes the lines that could not be added to a file in the format `multi_add_series_from_file` accepts,
+    /// grouped by the reason, which is added as a comment.
+    pub fn write_unresolved(&self, file_path: &std::path::Path) -> anyhow::Result<()> {
+        let mut contents = String::new();
+        if !self.not_found.is_empty() {
+            contents += "# Not found\n";
+            for line in self.not_found.iter() {
+                contents += &format!("{line}\n");
+            }
+        }
+        if !self.ambiguous.is_empty() {
+            contents += "# Ambiguous: add the first air year, use an external ID, or use --pick\n";
+            for line in self.ambiguous.iter() {
+                contents += &format!("{line}\n");
+            }
+        }
+        if !self.errored.is_empty() {
+            contents += "# Errored\n";
+            for (line, err) in self.errored.iter() {
+                contents += &format!("# {err}\n{line}\n");
+            }
         }
+
+        std::fs::write(file_path, contents)
+            .with_context(|| format!("Writing unresolved lines to {file_path:?}"))
     }
+}
 
-    for line in std::fs::read_to_string(file_path)?.lines() {
+impl std::fmt::Display for ImportSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} added, {} already tracked, {} not found, {} ambiguous, {} errored",
+            self.added,
+            self.already_tracked,
+            self.not_found.len(),
+            self.ambiguous.len(),
+            self.errored.len()
+        )
+    }
+}
+
+/// Adds the series listed in the file. Lines that can't be added don't stop the rest from being added,
+/// they are collected in the returned summary, and written to `unresolved_path` if given.
+pub fn multi_add_series_from_file(
+    ctx: &mut AppContext,
+    file_path: &std::path::Path,
+    pick: Option<PickStrategy>,
+    unresolved_path: Option<&std::path::Path>,
+    user: &db::User,
+) -> anyhow::Result<ImportSummary> {
+    log::info!("Adding all series from file: {file_path:?}");
+
+    let contents = std::fs::read_to_string(file_path)
+        .with_context(|| format!("Reading series file {file_path:?}"))?;
+
+    let mut summary = ImportSummary::default();
+    for line in contents.lines() {
+        let line = line.trim();
         if line.is_empty() || line.starts_with('#') {
             continue;
         }
 
-        let (title, first_air_year) = parse_line(line);
+        let matched = match parse_line(line) {
+            SeriesLine::Title(title, first_air_year) => {
+                match_title(ctx, title, first_air_year, pick)
+            }
+            SeriesLine::ExternalId(source, external_id) => {
+                match_external_id(ctx, source, external_id, pick)
+            }
+        };
+        summary.add_match(ctx, line, matched, user);
+    }
+
+    log::info!("Done adding series from file {file_path:?}: {summary}");
 
-        add_series_by_title(ctx, title, first_air_year)?;
+    // written even if everything was resolved, so that a second pass doesn't pick up stale lines
+    if let Some(unresolved_path) = unresolved_path {
+        summary.write_unresolved(unresolved_path)?;
+        log::info!(
+            "Wrote {} unresolved line(s) to {unresolved_path:?}",
+            summary.unresolved_count()
+        );
     }
 
-    Ok(())
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn adds_series_found_by_title_along_with_its_seasons() {
+        let mut ctx = testing::make_context();
+        let alice = testing::insert_user(&mut ctx, "Alice", "alice@example.com");
+
+        let id = SeriesId(226285);
+        let details = ctx.tmdb.get_series_details(id).unwrap();
+        let season = tmdb::SeasonDetails {
+            id: 1,
+            season_number: 1,
+            name: "Season 1".to_owned(),
+            air_date: details.first_air_date,
+            episodes: [&details.last_episode_to_air, &details.next_episode_to_air]
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect(),
+        };
+        ctx.tmdb = Box::new(tmdb::FixtureClient::new().with_season_details(id, season));
+
+        assert!(
+            !add_series_by_title(
+                &mut ctx,
+                "Elsbeth",
+                Some(2023),
+                PickStrategy::Newest,
+                false,
+                &alice
+            )
+            .unwrap()
+        );
+        assert!(ctx.db.get_all_series().unwrap().is_empty());
+
+        assert!(
+            add_series_by_title(
+                &mut ctx,
+                "Elsbeth",
+                Some(2024),
+                PickStrategy::Newest,
+                false,
+                &alice
+            )
+            .unwrap()
+        );
+
+        let series = ctx.db.get_series_by_id(id).unwrap().unwrap();
+        assert_eq!(series.title, "Elsbeth");
+        assert_eq!(series.status, details.status);
+        assert_eq!(series.next_episode_air_date, details.next_episode_date());
+        assert_eq!(
+            ctx.db
+                .get_all_series_tracked_by_user(alice.id)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(ctx.db.get_seasons_of_series(id).unwrap().len(), 1);
+        assert_eq!(ctx.db.get_episodes_of_series(id).unwrap().len(), 2);
+
+        let poster = ctx.db.get_poster_by_id(series.poster_id).unwrap().unwrap();
+        assert_eq!(
+            poster.img_data.as_ref(),
+            format!("poster {}", details.poster_path).as_bytes()
+        );
+    }
+
+    fn make_candidate(
+        id: i32,
+        name: &str,
+        first_air_date: &str,
+        popularity: f64,
+    ) -> tmdb::SeriesFound {
+        tmdb::SeriesFound {
+            id: SeriesId(id),
+            name: name.to_owned(),
+            overview: format!("Overview of {name}"),
+            first_air_date: first_air_date.parse::<chrono::NaiveDate>().unwrap().into(),
+            popularity,
+            origin_country: vec!["US".to_owned()],
+        }
+    }
+
+    #[test]
+    fn pick_strategies_prefer_exact_title_matches() {
+        let candidates = vec![
+            make_candidate(1, "Shogun", "1980-09-15", 20.0),
+            make_candidate(2, "Shōgun", "2024-02-27", 500.0),
+            make_candidate(3, "Shogun", "2024-02-27", 100.0),
+            make_candidate(4, "Shogun", "2001-01-01", 150.0),
+        ];
+        let pick = |strategy| {
+            pick_candidate(candidates.clone(), "Shogun", strategy)
+                .unwrap()
+                .id
+        };
+
+        assert_eq!(pick(PickStrategy::First), SeriesId(1));
+        assert_eq!(pick(PickStrategy::Newest), SeriesId(3));
+        assert_eq!(pick(PickStrategy::MostPopular), SeriesId(4));
+        assert!(pick_candidate(Vec::new(), "Shogun", PickStrategy::Newest).is_none());
+    }
+
+    #[test]
+    fn interactive_choice_reprompts_until_valid() {
+        let candidates = [
+            make_candidate(1, "Shogun", "1980-09-15", 20.0),
+            make_candidate(2, "Shogun", "2024-02-27", 100.0),
+        ];
+
+        let mut input = std::io::Cursor::new("3\nfoo\n2\n");
+        let mut output = Vec::new();
+        let choice = choose_candidate(&candidates, &mut input, &mut output).unwrap();
+        assert_eq!(choice, Some(1));
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains(
+            " 1) Shogun (1980) [US] | popularity 20.0 | TMDB ID 1\n    Overview of Shogun\n"
+        ));
+        assert!(output.contains(" 2) Shogun (2024) [US] | popularity 100.0 | TMDB ID 2\n"));
+        assert!(output.contains("Invalid choice: 3\n"));
+        assert!(output.contains("Invalid choice: foo\n"));
+
+        let mut input = std::io::Cursor::new("\n");
+        assert_eq!(
+            choose_candidate(&candidates, &mut input, &mut Vec::new()).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_title_and_external_id_lines() {
+        assert_eq!(
+            parse_line("Elsbeth (2024)"),
+            SeriesLine::Title("Elsbeth", Some(2024))
+        );
+        assert_eq!(
+            parse_line(" 3 Body Problem "),
+            SeriesLine::Title("3 Body Problem", None)
+        );
+        assert_eq!(
+            parse_line("imdb:tt13016388"),
+            SeriesLine::ExternalId(tmdb::ExternalSource::Imdb, "tt13016388")
+        );
+        assert_eq!(
+            parse_line("wikidata:Q98487963"),
+            SeriesLine::ExternalId(tmdb::ExternalSource::Wikidata, "Q98487963")
+        );
+        assert_eq!(
+            parse_line("Star Trek: Picard"),
+            SeriesLine::Title("Star Trek: Picard", None)
+        );
+    }
+
+    #[test]
+    fn adds_series_by_external_id() {
+        let mut ctx = testing::make_context();
+        let alice = testing::insert_user(&mut ctx, "Alice", "alice@example.com");
+
+        assert!(
+            add_series_by_external_id(&mut ctx, tmdb::ExternalSource::Tvdb, "tt13016388", &alice)
+                .is_err()
+        );
+        assert!(
+            !add_series_by_external_id(&mut ctx, tmdb::ExternalSource::Tvdb, "13016388", &alice)
+                .unwrap()
+        );
+        assert!(
+            add_series_by_external_id(&mut ctx, tmdb::ExternalSource::Imdb, "tt13016388", &alice)
+                .unwrap()
+        );
+
+        let tracked = ctx.db.get_all_series_tracked_by_user(alice.id).unwrap();
+        assert_eq!(tracked.len(), 1);
+        assert_eq!(tracked[0].tmdb_id, SeriesId(108545));
+        assert_eq!(tracked[0].title, "3 Body Problem");
+        assert_eq!(
+            tracked[0].external_ids.imdb_id.as_deref(),
+            Some("tt13016388")
+        );
+    }
+
+    #[test]
+    fn multi_add_continues_past_failures_and_collects_unresolved_lines() {
+        let mut ctx = testing::make_context();
+        let alice = testing::insert_user(&mut ctx, "Alice", "alice@example.com");
+
+        let shoguns = tmdb::SearchResults {
+            page: 1,
+            results: vec![
+                make_candidate(1, "Shogun", "1980-09-15", 20.0),
+                make_candidate(2, "Shogun", "2024-02-27", 100.0),
+            ],
+            total_pages: 1,
+            total_results: 2,
+        };
+        // the series found by this ID doesn't exist, so adding it fails
+        let missing = make_candidate(3, "Missing", "2024-01-01", 1.0);
+        ctx.tmdb = Box::new(
+            tmdb::FixtureClient::new()
+                .with_search_results("Shogun", shoguns)
+                .with_find_results(tmdb::ExternalSource::Tvdb, "12345", vec![missing]),
+        );
+
+        let dir = std::env::temp_dir().join(format!("tvtrack-add-from-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file_path = dir.join("series.txt");
+        let unresolved_path = dir.join("unresolved.txt");
+        std::fs::write(
+            &file_path,
+            "# my series\nimdb:tt13016388\nShogun\ntvdb:12345\nNo Such Series (2020)\n\nimdb:tt13016388\n",
+        )
+        .unwrap();
+
+        let summary =
+            multi_add_series_from_file(&mut ctx, &file_path, None, Some(&unresolved_path), &alice)
+                .unwrap();
+        let unresolved = std::fs::read_to_string(&unresolved_path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(summary.added, 1);
+        assert_eq!(summary.already_tracked, 1);
+        assert_eq!(summary.not_found, ["No Such Series (2020)"]);
+        assert_eq!(summary.ambiguous, ["Shogun"]);
+        assert_eq!(summary.errored.len(), 1);
+        assert_eq!(summary.errored[0].0, "tvdb:12345");
+        assert_eq!(
+            summary.to_string(),
+            "1 added, 1 already tracked, 1 not found, 1 ambiguous, 1 errored"
+        );
+
+        let tracked = ctx.db.get_all_series_tracked_by_user(alice.id).unwrap();
+        assert_eq!(tracked.len(), 1);
+        assert_eq!(tracked[0].tmdb_id, SeriesId(108545));
+
+        // the unresolved lines can be fed back as they are, the reasons are comments
+        let lines: Vec<_> = unresolved
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect();
+        assert_eq!(lines, ["No Such Series (2020)", "Shogun", "tvdb:12345"]);
+    }
+
+    #[test]
+    fn failed_add_leaves_no_partial_series_behind() {
+        for (operation, table) in [("INSERT", "episodes"), ("INSERT", "tracked_series")] {
+            let mut ctx = testing::make_context();
+            let alice = testing::insert_user(&mut ctx, "Alice", "alice@example.com");
+            testing::inject_failure(&mut ctx, operation, table);
+
+            let err = add_series_by_id(&mut ctx, SeriesId(108545), &alice).unwrap_err();
+            assert!(format!("{err:#}").contains("injected failure"));
+            for table in ["posters", "series", "seasons", "episodes", "tracked_series"] {
+                assert_eq!(testing::count_rows(&mut ctx, table), 0, "{table}");
+            }
+
+            // nothing is left behind that gets in the way of trying again
+            ctx.db
+                .conn
+                .execute_batch(&format!("DROP TRIGGER fail_{operation}_{table}"))
+                .unwrap();
+            assert_eq!(
+                add_series_by_id(&mut ctx, SeriesId(108545), &alice).unwrap(),
+                AddOutcome::Added
+            );
+            assert_eq!(testing::count_rows(&mut ctx, "posters"), 1);
+            assert_eq!(testing::count_rows(&mut ctx, "tracked_series"), 1);
+        }
+    }
 }
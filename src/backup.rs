@@ -0,0 +1,256 @@
+//! Backups of the database, taken before every command that changes it if configured, see `config::BackupConfig`.
+//!
+//! Backups are named after the time they were taken and the command they were taken for,
+//! e.g. `tvtrack-20240510T060000123Z-update.db`, so that they sort chronologically.
+
+use anyhow::{Context, bail};
+
+use crate::config::BackupConfig;
+
+use super::AppContext;
+
+const FILE_NAME_PREFIX: &str = "tvtrack-";
+const FILE_NAME_EXTENSION: &str = ".db";
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%3fZ";
+
+#[derive(Debug, PartialEq)]
+pub struct BackupFile {
+    pub path: std::path::PathBuf,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub reason: String,
+}
+
+impl BackupFile {
+    /// Returns `None` if the file name is not that of a backup.
+    fn from_path(path: std::path::PathBuf) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+        let (timestamp, reason) = file_name
+            .strip_prefix(FILE_NAME_PREFIX)?
+            .strip_suffix(FILE_NAME_EXTENSION)?
+            .split_once('-')?;
+        let timestamp = chrono::NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT)
+            .ok()?
+            .and_utc();
+        let reason = reason.to_owned();
+
+        Some(Self {
+            path,
+            timestamp,
+            reason,
+        })
+    }
+}
+
+fn make_file_name(timestamp: chrono::DateTime<chrono::Utc>, reason: &str) -> String {
+    format!(
+        "{FILE_NAME_PREFIX}{}-{reason}{FILE_NAME_EXTENSION}",
+        timestamp.format(TIMESTAMP_FORMAT)
+    )
+}
+
+/// All backups in the directory, newest first.
+pub fn list_backups(directory: &std::path::Path) -> anyhow::Result<Vec<BackupFile>> {
+    let mut backups = Vec::new();
+    let entries = std::fs::read_dir(directory)
+        .with_context(|| format!("Listing backup directory {directory:?}"))?;
+    for entry in entries {
+        let entry = entry.with_context(|| format!("Listing backup directory {directory:?}"))?;
+        if let Some(backup) = BackupFile::from_path(entry.path()) {
+            backups.push(backup);
+        }
+    }
+
+    backups.sort_by_key(|b| std::cmp::Reverse(b.timestamp));
+    Ok(backups)
+}
+
+/// Backs up the database into the configured directory, without deleting old backups.
+pub fn take_backup(
+    ctx: &mut AppContext,
+    config: &BackupConfig,
+    reason: &str,
+) -> anyhow::Result<BackupFile> {
+    std::fs::create_dir_all(&config.directory)
+        .with_context(|| format!("Creating backup directory {:?}", config.directory))?;
+
+    let timestamp = chrono::Utc::now();
+    let path = config.directory.join(make_file_name(timestamp, reason));
+    ctx.db.backup_to(&path)?;
+    log::info!("Backed up the database to {path:?}");
+
+    Ok(BackupFile {
+        path,
+        timestamp,
+        reason: reason.to_owned(),
+    })
+}
+
+/// Deletes the backups that are no longer to be kept according to the configured retention.
+/// Returns the number of deleted backups.
+pub fn prune_backups(
+    config: &BackupConfig,
+    now: chrono::DateTime<chrono::Utc>,
+) -> anyhow::Result<usize> {
+    let mut deleted_count = 0;
+    for (idx, backup) in list_backups(&config.directory)?.into_iter().enumerate() {
+        if config.keep(idx, now - backup.timestamp) {
+            continue;
+        }
+
+        log::debug!("Deleting old backup {:?}", backup.path);
+        std::fs::remove_file(&backup.path)
+            .with_context(|| format!("Deleting old backup {:?}", backup.path))?;
+        deleted_count += 1;
+    }
+
+    if deleted_count > 0 {
+        log::info!("Deleted {deleted_count} old backup(s)");
+    }
+    Ok(deleted_count)
+}
+
+/// Backs up the database before running a command that changes it, if backups are configured.
+pub fn backup_before(ctx: &mut AppContext, command: &str) -> anyhow::Result<()> {
+    let Some(config) = ctx.config.backup.clone() else {
+        return Ok(());
+    };
+
+    take_backup(ctx, &config, command)
+        .with_context(|| format!("Backing up the database before {command}"))?;
+    prune_backups(&config, chrono::Utc::now())?;
+    Ok(())
+}
+
+/// Restores the database from a backup, given either by its path or its file name in the backup directory.
+/// The current state of the database is backed up first, so that the restore can be undone.
+/// Without a backup given, lists the available backups instead.
+pub fn restore(ctx: &mut AppContext, backup: Option<&str>) -> anyhow::Result<()> {
+    let Some(config) = ctx.config.backup.clone() else {
+        bail!("Backups are not configured, see `backup` in the config file");
+    };
+
+    let Some(backup) = backup else {
+        for backup in list_backups(&config.directory)? {
+            println!(
+                "{} | {} | {}",
+                backup.timestamp,
+                backup.reason,
+                backup.path.display()
+            );
+        }
+        return Ok(());
+    };
+
+    let path = std::path::PathBuf::from(backup);
+    let path = if path.is_file() {
+        path
+    } else {
+        config.directory.join(backup)
+    };
+    if !path.is_file() {
+        bail!("No such backup: {backup}");
+    }
+
+    take_backup(ctx, &config, "restore").context("Backing up the database before restoring")?;
+    ctx.db.restore_from(&path)?;
+    log::info!("Restored the database from {path:?}");
+
+    prune_backups(&config, chrono::Utc::now())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn make_backup_config(name: &str) -> BackupConfig {
+        let directory =
+            std::env::temp_dir().join(format!("tvtrack-backups-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        BackupConfig {
+            directory,
+            keep_last: None,
+            keep_days: None,
+        }
+    }
+
+    #[test]
+    fn restores_the_database_from_a_backup() {
+        let mut ctx = testing::make_context();
+        ctx.config.backup = Some(make_backup_config("restore"));
+        testing::insert_user(&mut ctx, "Alice", "alice@example.com");
+
+        backup_before(&mut ctx, "add").unwrap();
+        ctx.db.conn.execute("DELETE FROM users", ()).unwrap();
+
+        let directory = ctx.config.backup.as_ref().unwrap().directory.clone();
+        let backups = list_backups(&directory).unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(backups[0].reason, "add");
+
+        let file_name = backups[0].path.file_name().unwrap().to_str().unwrap();
+        restore(&mut ctx, Some(file_name)).unwrap();
+        let users = ctx.db.get_all_users().unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].name, "Alice");
+
+        // the state before restoring was backed up as well
+        let mut reasons: Vec<_> = list_backups(&directory)
+            .unwrap()
+            .into_iter()
+            .map(|b| b.reason)
+            .collect();
+        reasons.sort();
+        assert_eq!(reasons, ["add", "restore"]);
+
+        assert!(restore(&mut ctx, Some("no-such-backup.db")).is_err());
+        assert_eq!(list_backups(&directory).unwrap().len(), 2);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn prunes_backups_beyond_the_configured_retention() {
+        let mut config = make_backup_config("prune");
+        std::fs::create_dir_all(&config.directory).unwrap();
+
+        let now: chrono::DateTime<chrono::Utc> = "2024-05-10T06:00:00Z".parse().unwrap();
+        for days_ago in [0, 1, 2, 5, 10] {
+            let timestamp = now - chrono::Duration::days(days_ago);
+            let path = config.directory.join(make_file_name(timestamp, "update"));
+            std::fs::write(path, "").unwrap();
+        }
+        // not a backup, so it's never deleted
+        std::fs::write(config.directory.join("notes.txt"), "").unwrap();
+
+        let days_ago = |config: &BackupConfig| {
+            list_backups(&config.directory)
+                .unwrap()
+                .iter()
+                .map(|b| (now - b.timestamp).num_days())
+                .collect::<Vec<_>>()
+        };
+
+        // nothing is deleted without a retention
+        assert_eq!(prune_backups(&config, now).unwrap(), 0);
+        assert_eq!(days_ago(&config), [0, 1, 2, 5, 10]);
+
+        config.keep_days = Some(6);
+        assert_eq!(prune_backups(&config, now).unwrap(), 1);
+        assert_eq!(days_ago(&config), [0, 1, 2, 5]);
+
+        // backups are kept if either rule says so
+        config.keep_last = Some(3);
+        config.keep_days = Some(1);
+        assert_eq!(prune_backups(&config, now).unwrap(), 1);
+        assert_eq!(days_ago(&config), [0, 1, 2]);
+
+        config.keep_days = None;
+        config.keep_last = Some(1);
+        assert_eq!(prune_backups(&config, now).unwrap(), 2);
+        assert_eq!(days_ago(&config), [0]);
+
+        assert!(config.directory.join("notes.txt").is_file());
+        std::fs::remove_dir_all(&config.directory).unwrap();
+    }
+}
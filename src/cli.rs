@@ -1,6 +1,8 @@
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::tmdb::{ExternalSource, SeriesStatus};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -19,17 +21,319 @@ pub enum Command {
     AddByTitle {
         title: String,
         first_air_year: Option<i32>,
+
+        /// List the search results and ask which one to add.
+        #[arg(short, long, conflicts_with = "pick")]
+        interactive: bool,
+
+        /// Which of the search results to add, preferring exact title matches.
+        #[arg(long, value_enum, default_value_t = PickStrategy::Newest)]
+        pick: PickStrategy,
+
+        /// The user to track the series for (ID, name or e-mail address).
+        /// May be omitted if there is only one user.
+        #[arg(short, long)]
+        user: Option<String>,
     },
     AddById {
         tmdb_id: i32,
+
+        /// The user to track the series for (ID, name or e-mail address).
+        /// May be omitted if there is only one user.
+        #[arg(short, long)]
+        user: Option<String>,
+    },
+    /// Add a series by its ID in another database, such as an IMDb ID like tt13016388.
+    AddByExternalId {
+        external_id: String,
+
+        #[arg(short, long, value_enum, default_value_t = ExternalSource::Imdb)]
+        source: ExternalSource,
+
+        /// The user to track the series for (ID, name or e-mail address).
+        /// May be omitted if there is only one user.
+        #[arg(short, long)]
+        user: Option<String>,
     },
+    /// Add all series listed in a file, one per line: either a title optionally followed by the first air year
+    /// in parens, e.g. "Elsbeth (2024)", or an external ID prefixed by its source, e.g. "imdb:tt13016388".
+    /// Lines that can't be added are skipped, and reported at the end.
     AddFrom {
         file_path: PathBuf,
+
+        /// How to pick among several series matching a line. If not given, such lines are reported as ambiguous
+        /// and not added.
+        #[arg(long, value_enum)]
+        pick: Option<PickStrategy>,
+
+        /// Write the lines that could not be added (not found, ambiguous or errored) to this file,
+        /// so that they can be fixed and added in a second pass.
+        #[arg(long)]
+        unresolved: Option<PathBuf>,
+
+        /// The user to track the series for (ID, name or e-mail address).
+        /// May be omitted if there is only one user.
+        #[arg(short, long)]
+        user: Option<String>,
+    },
+    /// Add all series found in an export of another tracker, see the `importer` module for the supported formats.
+    /// Entries that can't be added are skipped, and reported at the end.
+    ImportFrom {
+        #[arg(value_enum)]
+        format: ImportFormat,
+
+        file_path: PathBuf,
+
+        /// How to pick among several series matching an entry. If not given, such entries are reported as ambiguous
+        /// and not added.
+        #[arg(long, value_enum)]
+        pick: Option<PickStrategy>,
+
+        /// Write the entries that could not be added (not found, ambiguous or errored) to this file, in the format
+        /// `add-from` accepts, so that they can be fixed and added in a second pass.
+        #[arg(long)]
+        unresolved: Option<PathBuf>,
+
+        /// The user to track the series for (ID, name or e-mail address).
+        /// May be omitted if there is only one user.
+        #[arg(short, long)]
+        user: Option<String>,
     },
     Update {
         tmdb_id: Option<i32>,
 
         #[arg(short, long)]
         force: Option<bool>,
+
+        /// Fetch updates and report what would change and who would be notified,
+        /// without updating the database or sending any e-mails.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Stop tracking a series.
+    Remove {
+        /// TMDB ID or exact title of the series.
+        series: String,
+
+        /// The user to stop tracking the series for (ID, name or e-mail address).
+        /// May be omitted if there is only one user.
+        #[arg(short, long)]
+        user: Option<String>,
+
+        /// Also delete the series and its poster from the database if no users track it any more.
+        #[arg(long)]
+        purge: bool,
+    },
+    /// Subscribe a user to a series that is already tracked.
+    Subscribe {
+        /// TMDB ID or exact title of the series.
+        series: String,
+
+        /// The user to subscribe (ID, name or e-mail address).
+        #[arg(short, long)]
+        user: String,
+    },
+    /// Unsubscribe a user from a series, keeping the series in the database.
+    Unsubscribe {
+        /// TMDB ID or exact title of the series.
+        series: String,
+
+        /// The user to unsubscribe (ID, name or e-mail address).
+        #[arg(short, long)]
+        user: String,
+    },
+    /// Search TMDB for series without adding anything.
+    Search {
+        title: String,
+        first_air_year: Option<i32>,
+
+        /// How many pages of (20) results to fetch at most.
+        #[arg(long, default_value_t = 3)]
+        max_pages: i32,
+
+        /// Print the results as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Link a series to another TMDB series, e.g. after it was deleted or merged on TMDB, keeping all subscriptions.
+    Relink {
+        /// TMDB ID or exact title of the series.
+        series: String,
+
+        /// TMDB ID of the series to link it to. If omitted, TMDB is searched for the title of the series.
+        #[arg(long)]
+        tmdb_id: Option<i32>,
+    },
+    /// Show all recorded changes of a series, oldest first.
+    History {
+        /// The TMDB ID or title of the series.
+        series: String,
+    },
+    /// Export the air dates of all known upcoming episodes as an iCalendar (.ics) file.
+    ExportIcal {
+        file_path: PathBuf,
+
+        /// Only export series tracked by the given user (ID, name or e-mail address).
+        #[arg(short, long)]
+        user: Option<String>,
+    },
+    /// Export all users and the series they track to a file, to back them up or move them to another database.
+    Export {
+        file_path: PathBuf,
+
+        /// Guessed from the file extension if not given, defaulting to JSON.
+        #[arg(short, long, value_enum)]
+        format: Option<ExportFormat>,
+    },
+    /// Restore the users and the series they track from a file made by `export` into an empty database.
+    /// The series are fetched from TMDB again.
+    Import {
+        file_path: PathBuf,
+
+        /// Guessed from the file extension if not given, defaulting to JSON.
+        #[arg(short, long, value_enum)]
+        format: Option<ExportFormat>,
+    },
+    /// Restore the database from a backup, see `backup` in the config file.
+    /// The current state of the database is backed up first.
+    Restore {
+        /// The path or file name of the backup. If omitted, the available backups are listed.
+        backup: Option<String>,
+    },
+    /// Manage users.
+    User {
+        #[command(subcommand)]
+        command: UserCommand,
+    },
+    /// List all tracked series along with their status and episode information.
+    List {
+        /// Only list series with the given status.
+        #[arg(short, long)]
+        status: Option<SeriesStatus>,
+
+        /// Only list series tracked by the given user (ID, name or e-mail address).
+        #[arg(short, long)]
+        user: Option<String>,
+
+        #[arg(long, value_enum, default_value_t = ListSortKey::Title)]
+        sort: ListSortKey,
+
+        /// Also list all known seasons and episodes of each series.
+        #[arg(short, long)]
+        episodes: bool,
+    },
+}
+
+impl Command {
+    /// The name of the backup to take before running the command, or `None` if it doesn't change the database.
+    /// `restore` takes its own backup.
+    pub fn backup_name(&self) -> Option<&'static str> {
+        match self {
+            Command::AddByTitle { .. } => Some("add-by-title"),
+            Command::AddById { .. } => Some("add-by-id"),
+            Command::AddByExternalId { .. } => Some("add-by-external-id"),
+            Command::AddFrom { .. } => Some("add-from"),
+            Command::ImportFrom { .. } => Some("import-from"),
+            Command::Update { dry_run: false, .. } => Some("update"),
+            Command::Remove { .. } => Some("remove"),
+            Command::Subscribe { .. } => Some("subscribe"),
+            Command::Unsubscribe { .. } => Some("unsubscribe"),
+            Command::Relink { .. } => Some("relink"),
+            Command::Import { .. } => Some("import"),
+            Command::User {
+                command: UserCommand::Add { .. },
+            } => Some("user-add"),
+            Command::User {
+                command: UserCommand::Remove { .. },
+            } => Some("user-remove"),
+            Command::Update { dry_run: true, .. }
+            | Command::Search { .. }
+            | Command::History { .. }
+            | Command::ExportIcal { .. }
+            | Command::Export { .. }
+            | Command::Restore { .. }
+            | Command::User {
+                command: UserCommand::List,
+            }
+            | Command::List { .. } => None,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum UserCommand {
+    Add {
+        name: String,
+        email: String,
+    },
+    List,
+    /// Remove a user along with all their subscriptions.
+    Remove {
+        /// ID, name or e-mail address of the user.
+        user: String,
     },
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    /// A Trakt JSON backup of shows, e.g. the watchlist.
+    Trakt,
+    /// A TV Time CSV export of the followed shows.
+    TvTime,
+    /// An IMDb list or watchlist CSV export.
+    ImdbList,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PickStrategy {
+    /// The most relevant one according to TMDB.
+    First,
+    /// The one released most recently, preferring released series over unreleased ones.
+    Newest,
+    MostPopular,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ListSortKey {
+    Title,
+    FirstAirDate,
+    NextEpisode,
+    LastUpdate,
+    NextUpdate,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn commands_that_change_the_database_are_backed_up() {
+        let backup_name = |args: &[&str]| {
+            Args::try_parse_from([&["tvtrack"], args].concat())
+                .unwrap()
+                .command
+                .backup_name()
+        };
+
+        assert_eq!(backup_name(&["update"]), Some("update"));
+        assert_eq!(backup_name(&["update", "--dry-run"]), None);
+        assert_eq!(
+            backup_name(&["unsubscribe", "Elsbeth", "-u", "alice"]),
+            Some("unsubscribe")
+        );
+        assert_eq!(
+            backup_name(&["user", "remove", "alice"]),
+            Some("user-remove")
+        );
+        assert_eq!(backup_name(&["user", "list"]), None);
+        assert_eq!(backup_name(&["import", "export.json"]), Some("import"));
+        assert_eq!(backup_name(&["export", "export.json"]), None);
+    }
+}
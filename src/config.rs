@@ -1,5 +1,6 @@
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StateFilePath(pub String);
@@ -18,6 +19,16 @@ pub struct AppConfig {
     pub tmdb: TMDBConfig,
     pub smtp: SMTPConfig,
     pub emails: EmailsConfig,
+
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+
+    #[serde(default)]
+    pub webhook: Option<WebhookConfig>,
+
+    /// If set, the database is backed up before every command that changes it.
+    #[serde(default)]
+    pub backup: Option<BackupConfig>,
 }
 
 impl AppConfig {
@@ -33,6 +44,56 @@ impl AppConfig {
 pub struct TMDBConfig {
     pub api_key: String,
     pub api_access_token: String,
+
+    /// Root URL of the TMDB API, which can be pointed e.g. at a local mock server.
+    #[serde(default = "TMDBConfig::default_api_base_url")]
+    pub api_base_url: String,
+
+    #[serde(default = "TMDBConfig::default_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// How many times to try a request before giving up, if it fails with a network error, HTTP 429 or 5xx.
+    #[serde(default = "TMDBConfig::default_max_attempts")]
+    pub max_attempts: u32,
+
+    /// Delay before the first retry, roughly doubled after every failed attempt.
+    /// On HTTP 429 responses, the `Retry-After` header takes precedence.
+    #[serde(default = "TMDBConfig::default_retry_delay_ms")]
+    pub retry_delay_ms: u64,
+
+    /// The longest `Retry-After` delay to wait for; if the server asks for more, the request fails instead.
+    #[serde(default = "TMDBConfig::default_max_retry_after_secs")]
+    pub max_retry_after_secs: u64,
+
+    /// Client-side rate limit; 0 means unlimited.
+    #[serde(default = "TMDBConfig::default_max_requests_per_second")]
+    pub max_requests_per_second: u32,
+}
+
+impl TMDBConfig {
+    pub fn default_api_base_url() -> String {
+        "https://api.themoviedb.org/3/".to_owned()
+    }
+
+    pub fn default_timeout_secs() -> u64 {
+        30
+    }
+
+    pub fn default_max_attempts() -> u32 {
+        4
+    }
+
+    pub fn default_retry_delay_ms() -> u64 {
+        1000
+    }
+
+    pub fn default_max_retry_after_secs() -> u64 {
+        60
+    }
+
+    pub fn default_max_requests_per_second() -> u32 {
+        20
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -49,3 +110,104 @@ pub struct EmailsConfig {
     pub from_name: Option<String>,
     pub from_address: String,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, strum::Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum NotificationChannel {
+    Email,
+    Webhook,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NotificationsConfig {
+    /// Channels to notify users on who don't have an entry in `users`.
+    #[serde(default = "NotificationsConfig::default_channels")]
+    pub default_channels: Vec<NotificationChannel>,
+
+    /// Channels to notify specific users on, keyed by the user's e-mail address.
+    #[serde(default)]
+    pub users: HashMap<String, Vec<NotificationChannel>>,
+}
+
+impl NotificationsConfig {
+    fn default_channels() -> Vec<NotificationChannel> {
+        vec![NotificationChannel::Email]
+    }
+
+    pub fn channels_for_user(&self, user_email: &str) -> &[NotificationChannel] {
+        self.users.get(user_email).unwrap_or(&self.default_channels)
+    }
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            default_channels: Self::default_channels(),
+            users: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    /// The JSON payload is POSTed to each of these.
+    pub urls: Vec<String>,
+
+    /// If set, requests are signed with HMAC-SHA256 using this secret, see `notify::webhook`.
+    #[serde(default)]
+    pub secret: Option<String>,
+
+    /// How many times to try delivering the payload to a URL before giving up.
+    #[serde(default = "WebhookConfig::default_max_attempts")]
+    pub max_attempts: u32,
+
+    /// Delay before the first retry, doubled after every failed attempt.
+    #[serde(default = "WebhookConfig::default_retry_delay_ms")]
+    pub retry_delay_ms: u64,
+
+    #[serde(default = "WebhookConfig::default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl WebhookConfig {
+    fn default_max_attempts() -> u32 {
+        3
+    }
+
+    fn default_retry_delay_ms() -> u64 {
+        1000
+    }
+
+    fn default_timeout_secs() -> u64 {
+        30
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupConfig {
+    pub directory: std::path::PathBuf,
+
+    /// Keep (at least) this many of the most recent backups.
+    #[serde(default)]
+    pub keep_last: Option<usize>,
+
+    /// Keep (at least) the backups made in this many days.
+    #[serde(default)]
+    pub keep_days: Option<u32>,
+}
+
+impl BackupConfig {
+    /// Whether the backup should be kept, given its index among the backups from newest to oldest, and its age.
+    /// If no retention is configured, all backups are kept.
+    pub fn keep(&self, newest_first_idx: usize, age: chrono::Duration) -> bool {
+        if self.keep_last.is_none() && self.keep_days.is_none() {
+            return true;
+        }
+
+        self.keep_last.is_some_and(|n| newest_first_idx < n)
+            || self
+                .keep_days
+                .is_some_and(|days| age < chrono::Duration::days(days.into()))
+    }
+}
@@ -3,5 +3,5 @@ use crate::{config::AppConfig, db::Db, tmdb};
 pub struct AppContext {
     pub config: AppConfig,
     pub db: Db,
-    pub tmdb: tmdb::Client,
+    pub tmdb: Box<dyn tmdb::Api>,
 }
@@ -0,0 +1,46 @@
+use super::table_model::TableModel;
+use crate::tmdb::{self, EpisodeDetails, EpisodeId, EpisodeType, OptionalDate};
+
+#[derive(Debug, Clone)]
+pub struct Episode {
+    pub id: EpisodeId,
+    #[allow(dead_code)]
+    pub series_tmdb_id: tmdb::SeriesId,
+    pub season_number: i32,
+    pub episode_number: i32,
+    pub name: String,
+    pub episode_type: EpisodeType,
+    pub air_date: OptionalDate,
+}
+
+impl Episode {
+    pub fn to_details(&self) -> EpisodeDetails {
+        EpisodeDetails {
+            id: self.id,
+            season_number: self.season_number,
+            episode_number: self.episode_number,
+            name: self.name.clone(),
+            episode_type: self.episode_type,
+            air_date: self.air_date,
+        }
+    }
+}
+
+impl TableModel for Episode {
+    fn table_name() -> &'static str {
+        "episodes"
+    }
+
+    fn from_full_row(row: &rusqlite::Row) -> anyhow::Result<Self> {
+        let result = Self {
+            id: row.get("id")?,
+            series_tmdb_id: row.get("series_tmdb_id")?,
+            season_number: row.get("season_number")?,
+            episode_number: row.get("episode_number")?,
+            name: row.get("name")?,
+            episode_type: row.get("episode_type")?,
+            air_date: row.get("air_date")?,
+        };
+        Ok(result)
+    }
+}
@@ -0,0 +1,209 @@
+use anyhow::{Context, bail};
+
+struct Migration {
+    description: &'static str,
+    sql: &'static str,
+}
+
+/// All schema migrations, in order. The schema version of a database is the number of migrations applied to it.
+/// Never modify or reorder existing entries, only append new ones.
+static MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "initial schema",
+        sql: include_str!("migrations/001_initial.sql"),
+    },
+    Migration {
+        description: "make posters.source_url non-optional",
+        sql: include_str!("migrations/002_poster_source_url_not_null.sql"),
+    },
+    Migration {
+        description: "add seasons and episodes",
+        sql: include_str!("migrations/003_seasons_and_episodes.sql"),
+    },
+    Migration {
+        description: "add series.orphaned_timestamp",
+        sql: include_str!("migrations/004_orphaned_series.sql"),
+    },
+    Migration {
+        description: "add external IDs of series",
+        sql: include_str!("migrations/005_series_external_ids.sql"),
+    },
+    Migration {
+        description: "add series history",
+        sql: include_str!("migrations/006_series_history.sql"),
+    },
+    Migration {
+        description: "make users.id an alias of the rowid in databases created from setup.sql",
+        sql: include_str!("migrations/007_users_integer_primary_key.sql"),
+    },
+];
+
+const SCHEMA_VERSION: i32 = MIGRATIONS.len() as i32;
+
+fn get_user_version(conn: &rusqlite::Connection) -> anyhow::Result<i32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+        .context("Querying PRAGMA user_version")
+}
+
+fn table_exists(conn: &rusqlite::Connection, table_name: &str) -> anyhow::Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+        (table_name,),
+        |row| row.get(0),
+    )
+    .with_context(|| format!("Checking whether table {table_name} exists"))
+}
+
+/// Fails if the database schema is not at `SCHEMA_VERSION`, without changing anything;
+/// for databases opened read-only, which can't be migrated.
+pub fn check_up_to_date(conn: &rusqlite::Connection) -> anyhow::Result<()> {
+    let version = get_user_version(conn)?;
+    if version > SCHEMA_VERSION {
+        bail!(
+            "Database schema version {version} is newer than the latest version {SCHEMA_VERSION} supported by this build of tvtrack; please upgrade tvtrack"
+        );
+    }
+    if version < SCHEMA_VERSION {
+        bail!(
+            "Database schema version {version} is older than the latest version {SCHEMA_VERSION}, and it can't be migrated while opened read-only"
+        );
+    }
+    Ok(())
+}
+
+/// Brings the database schema up to `SCHEMA_VERSION`, applying each pending migration in its own transaction.
+pub fn migrate(conn: &mut rusqlite::Connection) -> anyhow::Result<()> {
+    let mut version = get_user_version(conn)?;
+
+    // databases created by hand from the old setup.sql have the initial schema but no version recorded
+    if version == 0 && table_exists(conn, "series")? {
+        log::info!("Database has the initial schema but no schema version, assuming version 1");
+        conn.pragma_update(None, "user_version", 1)
+            .context("Setting PRAGMA user_version")?;
+        version = 1;
+    }
+
+    if version > SCHEMA_VERSION {
+        bail!(
+            "Database schema version {version} is newer than the latest version {SCHEMA_VERSION} supported by this build of tvtrack; please upgrade tvtrack"
+        );
+    }
+
+    for (idx, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let new_version = idx as i32 + 1;
+        log::info!(
+            "Migrating database schema to version {new_version}: {}",
+            migration.description
+        );
+
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql).with_context(|| {
+            format!(
+                "Applying migration {new_version} ({})",
+                migration.description
+            )
+        })?;
+        tx.pragma_update(None, "user_version", new_version)
+            .context("Setting PRAGMA user_version")?;
+        tx.commit()
+            .with_context(|| format!("Committing migration {new_version}"))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column_type(conn: &rusqlite::Connection, table_name: &str, column_name: &str) -> String {
+        conn.query_row(
+            "SELECT type FROM pragma_table_info(?) WHERE name = ?",
+            (table_name, column_name),
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn fresh_database_is_migrated_to_the_latest_version() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        assert_eq!(get_user_version(&conn).unwrap(), SCHEMA_VERSION);
+        assert!(table_exists(&conn, "series_history").unwrap());
+
+        // migrating again is a no-op
+        migrate(&mut conn).unwrap();
+        assert_eq!(get_user_version(&conn).unwrap(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn legacy_database_from_setup_sql_is_adopted() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        // the schema of the old data/setup.sql, which had no schema version
+        conn.execute_batch(
+            "create table series (tmdb_id int not null primary key, title text not null, first_air_date text, poster_id int references posters(id), status text, in_production int, last_episode_air_date text, next_episode_air_date text, details text, update_timestamp text);
+            create table posters (id int not null primary key, img_data blob not null, mime_type text not null, source_url text);
+            create table users (id int not null primary key, name text not null, email text not null);
+            create table tracked_series (user_id int not null references users(id), series_tmdb_id int not null references series(tmdb_id), start_timestamp text);
+            CREATE UNIQUE INDEX tracked_series_idx ON tracked_series(user_id, series_tmdb_id);
+            insert into users (id, name, email) values (1, 'Alice', 'alice@example.com');
+            insert into posters (id, img_data, mime_type) values (1, x'00', 'image/jpeg');
+            insert into series (tmdb_id, title, poster_id, details) values (1, 'Some Series', 1, '{}');
+            insert into tracked_series (user_id, series_tmdb_id) values (1, 1);",
+        )
+        .unwrap();
+        assert_eq!(column_type(&conn, "users", "id"), "INT");
+
+        migrate(&mut conn).unwrap();
+        assert_eq!(get_user_version(&conn).unwrap(), SCHEMA_VERSION);
+        assert_eq!(column_type(&conn, "users", "id"), "INTEGER");
+        assert_eq!(column_type(&conn, "posters", "id"), "INTEGER");
+
+        // existing data is kept, and new users and posters no longer need an explicit ID
+        let tracked: (i64, String) = conn
+            .query_row(
+                "SELECT users.id, series.title FROM tracked_series INNER JOIN users ON tracked_series.user_id = users.id INNER JOIN series ON tracked_series.series_tmdb_id = series.tmdb_id",
+                (),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(tracked, (1, "Some Series".to_owned()));
+        conn.execute(
+            "INSERT INTO users (name, email) VALUES ('Bob', 'bob@example.com')",
+            (),
+        )
+        .unwrap();
+        assert_eq!(conn.last_insert_rowid(), 2);
+        conn.execute(
+            "INSERT INTO posters (img_data, mime_type, source_url) VALUES (x'00', 'image/jpeg', '')",
+            (),
+        )
+        .unwrap();
+        assert_eq!(conn.last_insert_rowid(), 2);
+    }
+
+    #[test]
+    fn pending_migrations_fail_the_read_only_check() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        assert!(check_up_to_date(&conn).is_err());
+        migrate(&mut conn).unwrap();
+        check_up_to_date(&conn).unwrap();
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION - 1)
+            .unwrap();
+        assert!(check_up_to_date(&conn).is_err());
+    }
+
+    #[test]
+    fn database_newer_than_this_build_is_rejected() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+
+        let err = migrate(&mut conn).unwrap_err();
+        assert!(err.to_string().contains("please upgrade tvtrack"));
+        assert!(check_up_to_date(&conn).is_err());
+        assert_eq!(get_user_version(&conn).unwrap(), SCHEMA_VERSION + 1);
+    }
+}
@@ -21,14 +21,14 @@ create table series (
 );
 
 create table posters (
-    id int not null primary key,
+    id integer not null primary key,
     img_data blob not null,
     mime_type text not null,
     source_url text
 );
 
 create table users (
-    id int not null primary key,
+    id integer not null primary key,
     name text not null,
     email text not null
 );
@@ -39,6 +39,3 @@ create table tracked_series (
     start_timestamp text
 );
 CREATE UNIQUE INDEX tracked_series_idx ON tracked_series(user_id, series_tmdb_id);
-
-insert into users ( name, email )
-    values ( "Gabor", "id@gaborkozar.me" );
\ No newline at end of file
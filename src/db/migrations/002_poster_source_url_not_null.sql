@@ -0,0 +1,16 @@
+/*
+    SQLite cannot add a NOT NULL constraint to an existing column, so we have to rebuild the table.
+    Posters stored before we started recording the source URL get an empty one.
+*/
+create table posters_new (
+    id integer not null primary key,
+    img_data blob not null,
+    mime_type text not null,
+    source_url text not null
+);
+
+insert into posters_new (id, img_data, mime_type, source_url)
+    select id, img_data, mime_type, coalesce(source_url, '') from posters;
+
+drop table posters;
+alter table posters_new rename to posters;
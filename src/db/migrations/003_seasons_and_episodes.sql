@@ -0,0 +1,20 @@
+create table seasons (
+    id int not null primary key, /* TMDB season ID */
+    series_tmdb_id int not null references series(tmdb_id),
+    season_number int not null,
+    name text not null,
+    air_date text, /* may be null if unreleased */
+    update_timestamp text not null
+);
+CREATE UNIQUE INDEX seasons_idx ON seasons(series_tmdb_id, season_number);
+
+create table episodes (
+    id int not null primary key, /* TMDB episode ID */
+    series_tmdb_id int not null references series(tmdb_id),
+    season_number int not null,
+    episode_number int not null,
+    name text not null,
+    episode_type text not null,
+    air_date text /* may be null if unknown */
+);
+CREATE INDEX episodes_idx ON episodes(series_tmdb_id, season_number, episode_number);
@@ -0,0 +1,2 @@
+/* set when the series turned out to no longer exist on TMDB, e.g. because it was deleted or merged into another one */
+alter table series add column orphaned_timestamp text;
@@ -0,0 +1,4 @@
+/* IDs of the series in other databases, see tmdb::ExternalIds; null until the series is next updated */
+alter table series add column imdb_id text;
+alter table series add column tvdb_id int;
+alter table series add column wikidata_id text;
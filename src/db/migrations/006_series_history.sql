@@ -0,0 +1,9 @@
+/* every set of changes detected when updating a series, see update::SeriesDetailsChanges */
+create table series_history (
+    id integer not null primary key,
+    series_tmdb_id int not null references series(tmdb_id),
+    timestamp text not null,
+    changes text not null, /* json */
+    old_details text not null /* json, the details of the series before the changes */
+);
+CREATE INDEX series_history_idx ON series_history(series_tmdb_id, timestamp);
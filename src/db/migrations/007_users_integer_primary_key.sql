@@ -0,0 +1,17 @@
+/*
+    Databases created from the old data/setup.sql declared users.id as `int` rather than `integer`, which doesn't make it
+    an alias of the rowid, so users could only be inserted with an explicit ID. SQLite cannot change the type of a column,
+    so we have to rebuild the table, keeping the IDs of the existing users. posters.id had the same problem, but posters
+    were already rebuilt by migration 002. For databases created by migration 001 this is a no-op copy.
+*/
+create table users_new (
+    id integer not null primary key,
+    name text not null,
+    email text not null
+);
+
+insert into users_new (id, name, email)
+    select id, name, email from users;
+
+drop table users;
+alter table users_new rename to users;
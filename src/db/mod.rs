@@ -1,10 +1,17 @@
+mod episode;
+mod migrations;
 mod poster;
+mod season;
 mod series;
+mod series_history;
 mod table_model;
 mod user;
 
+pub use self::episode::Episode;
 pub use self::poster::{Poster, PosterId};
+pub use self::season::Season;
 pub use self::series::Series;
+pub use self::series_history::SeriesHistoryEntry;
 pub use self::user::User;
 
 use self::table_model::TableModel;
@@ -16,15 +23,86 @@ pub struct Db {
 }
 
 impl Db {
+    /// Opens the database, creating it if it doesn't exist yet, and migrates it to the latest schema version.
     pub fn open(file_path: &str) -> anyhow::Result<Self> {
+        let mut conn = rusqlite::Connection::open_with_flags(
+            file_path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE | rusqlite::OpenFlags::SQLITE_OPEN_CREATE,
+        )
+        .with_context(|| format!("Failed to open SQLite DB: {file_path}"))?;
+
+        migrations::migrate(&mut conn)
+            .with_context(|| format!("Migrating SQLite DB: {file_path}"))?;
+        Ok(Self { conn })
+    }
+
+    /// Opens an existing database without creating or migrating it, e.g. for dry runs.
+    /// Fails if the database needs to be migrated first.
+    pub fn open_read_only(file_path: &str) -> anyhow::Result<Self> {
         let conn = rusqlite::Connection::open_with_flags(
             file_path,
-            rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
         )
         .with_context(|| format!("Failed to open SQLite DB: {file_path}"))?;
+
+        migrations::check_up_to_date(&conn)
+            .with_context(|| format!("Checking schema version of SQLite DB: {file_path}"))?;
         Ok(Self { conn })
     }
 
+    /// Runs `f` in a transaction, which is committed if `f` succeeds and rolled back otherwise.
+    /// Unlike `rusqlite::Transaction`, this doesn't hold on to a borrow of the connection,
+    /// so all methods of `Db` can be used in `f`.
+    pub fn in_transaction<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        self.conn
+            .execute_batch("BEGIN")
+            .context("Beginning transaction")?;
+
+        let result = f(self).and_then(|value| {
+            self.conn
+                .execute_batch("COMMIT")
+                .context("Committing transaction")?;
+            Ok(value)
+        });
+
+        // a failed COMMIT leaves the transaction open as well
+        if result.is_err() && !self.conn.is_autocommit() {
+            if let Err(err) = self.conn.execute_batch("ROLLBACK") {
+                log::error!("Failed to roll back transaction: {err:?}");
+            }
+        }
+        result
+    }
+
+    /// Takes a consistent copy of the database while it is open, see https://www.sqlite.org/backup.html
+    pub fn backup_to(&self, file_path: &std::path::Path) -> anyhow::Result<()> {
+        self.conn
+            .backup(rusqlite::DatabaseName::Main, file_path, None)
+            .with_context(|| format!("Backing up SQLite DB to {file_path:?}"))
+    }
+
+    /// Replaces the contents of the database with those of the backup, and migrates it to the latest schema version.
+    pub fn restore_from(&mut self, file_path: &std::path::Path) -> anyhow::Result<()> {
+        // SQLite would create an empty database if the file doesn't exist
+        if !file_path.is_file() {
+            anyhow::bail!("No such backup file: {file_path:?}");
+        }
+
+        self.conn
+            .restore(
+                rusqlite::DatabaseName::Main,
+                file_path,
+                None::<fn(rusqlite::backup::Progress)>,
+            )
+            .with_context(|| format!("Restoring SQLite DB from {file_path:?}"))?;
+
+        migrations::migrate(&mut self.conn)
+            .with_context(|| format!("Migrating SQLite DB restored from {file_path:?}"))
+    }
+
     pub fn optional_single_row_result<T>(result: anyhow::Result<T>) -> anyhow::Result<Option<T>> {
         match result {
             Ok(row) => Ok(Some(row)),
@@ -37,7 +115,11 @@ impl Db {
     }
 
     pub fn get_by_id<T: TableModel>(&mut self, id: i64) -> anyhow::Result<Option<T>> {
-        let sql = format!("SELECT * FROM {} WHERE id = ? LIMIT 1", T::table_name());
+        let sql = format!(
+            "SELECT * FROM {} WHERE {} = ? LIMIT 1",
+            T::table_name(),
+            T::id_column_name()
+        );
 
         let result = self
             .conn
@@ -83,9 +165,34 @@ impl Db {
         Ok(result)
     }
 
+    /// Runs an arbitrary query returning full rows of `T`.
+    pub fn query_all<T: TableModel>(
+        &mut self,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> anyhow::Result<Vec<T>> {
+        let mut stmt = self
+            .conn
+            .prepare(sql)
+            .with_context(|| format!("Preparing query on {}: {sql}", T::table_name()))?;
+
+        let rows = stmt
+            .query_and_then(params, |row| {
+                T::from_full_row(row).with_context(|| {
+                    format!("Error deserializing {} row: {row:?}", T::table_name())
+                })
+            })
+            .with_context(|| format!("Querying {}: {sql}", T::table_name()))?;
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row?);
+        }
+        Ok(result)
+    }
+
     pub fn insert_series(&mut self, new_series: &Series) -> anyhow::Result<()> {
         self.conn.execute(
-            "INSERT INTO series (tmdb_id, title, first_air_date, poster_id, status, in_production, last_episode_air_date, next_episode_air_date, details, update_timestamp) VALUES (:tmdb_id, :title, :first_air_date, :poster_id, :status, :in_production, :last_episode_air_date, :next_episode_air_date, :details, :update_timestamp)",
+            "INSERT INTO series (tmdb_id, title, first_air_date, poster_id, status, in_production, last_episode_air_date, next_episode_air_date, details, update_timestamp, imdb_id, tvdb_id, wikidata_id) VALUES (:tmdb_id, :title, :first_air_date, :poster_id, :status, :in_production, :last_episode_air_date, :next_episode_air_date, :details, :update_timestamp, :imdb_id, :tvdb_id, :wikidata_id)",
             rusqlite::named_params! {
                 ":tmdb_id": new_series.tmdb_id,
                 ":title": new_series.title,
@@ -97,15 +204,272 @@ impl Db {
                 ":next_episode_air_date": new_series.next_episode_air_date,
                 ":details": new_series.details_json,
                 ":update_timestamp": new_series.update_timestamp,
+                ":imdb_id": new_series.external_ids.imdb_id,
+                ":tvdb_id": new_series.external_ids.tvdb_id,
+                ":wikidata_id": new_series.external_ids.wikidata_id,
             }
         ).with_context(|| format!("Inserting series {} into the database: {:?}", new_series.details.identify(), new_series))?;
         Ok(())
     }
 
+    pub fn get_series_by_title(&mut self, title: &str) -> anyhow::Result<Vec<Series>> {
+        self.query_all(
+            "SELECT * FROM series WHERE title = ? COLLATE NOCASE",
+            (title,),
+        )
+    }
+
+    /// Looks up a series by TMDB ID, or failing that, by title (case-insensitive).
+    /// Fails if multiple series have the same title.
+    pub fn find_series(&mut self, id_or_title: &str) -> anyhow::Result<Option<Series>> {
+        if let Ok(id) = id_or_title.parse::<i32>() {
+            if let Some(series) = self.get_series_by_id(tmdb::SeriesId(id))? {
+                return Ok(Some(series));
+            }
+        }
+
+        let mut matches = self.get_series_by_title(id_or_title)?;
+        if matches.len() > 1 {
+            anyhow::bail!(
+                "Multiple series are titled {id_or_title:?}, use the TMDB ID instead: {}",
+                matches
+                    .iter()
+                    .map(|s| s.details.identify())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        Ok(matches.pop())
+    }
+
     pub fn get_all_series(&mut self) -> anyhow::Result<Vec<Series>> {
         self.get_all::<Series>()
     }
 
+    pub fn get_all_series_tracked_by_user(&mut self, user_id: i64) -> anyhow::Result<Vec<Series>> {
+        self.query_all(
+            "SELECT series.* FROM tracked_series INNER JOIN series ON tracked_series.series_tmdb_id = series.tmdb_id WHERE tracked_series.user_id = ?",
+            (user_id,),
+        )
+    }
+
+    /// The series tracked by the user along with when the user started tracking them, ordered by title.
+    pub fn get_all_series_tracked_by_user_with_start(
+        &mut self,
+        user_id: i64,
+    ) -> anyhow::Result<Vec<(Series, Option<chrono::DateTime<chrono::Utc>>)>> {
+        let sql = "SELECT series.*, tracked_series.start_timestamp FROM tracked_series INNER JOIN series ON tracked_series.series_tmdb_id = series.tmdb_id WHERE tracked_series.user_id = ? ORDER BY series.title, series.tmdb_id";
+        let mut stmt = self
+            .conn
+            .prepare(sql)
+            .with_context(|| format!("Preparing query on tracked_series: {sql}"))?;
+
+        let rows = stmt
+            .query_and_then((user_id,), |row| {
+                let series = Series::from_full_row(row)
+                    .with_context(|| format!("Error deserializing tracked series row: {row:?}"))?;
+                let start_timestamp = row.get("start_timestamp")?;
+                anyhow::Ok((series, start_timestamp))
+            })
+            .with_context(|| format!("Querying tracked_series: {sql}"))?;
+        rows.collect()
+    }
+
+    pub fn get_seasons_of_series(
+        &mut self,
+        series_id: tmdb::SeriesId,
+    ) -> anyhow::Result<Vec<Season>> {
+        self.query_all(
+            "SELECT * FROM seasons WHERE series_tmdb_id = ? ORDER BY season_number",
+            (series_id,),
+        )
+    }
+
+    /// All known episodes of the series, in order.
+    pub fn get_episodes_of_series(
+        &mut self,
+        series_id: tmdb::SeriesId,
+    ) -> anyhow::Result<Vec<Episode>> {
+        self.query_all(
+            "SELECT * FROM episodes WHERE series_tmdb_id = ? ORDER BY season_number, episode_number",
+            (series_id,),
+        )
+    }
+
+    pub fn get_episodes_of_season(
+        &mut self,
+        series_id: tmdb::SeriesId,
+        season_number: i32,
+    ) -> anyhow::Result<Vec<Episode>> {
+        self.query_all(
+            "SELECT * FROM episodes WHERE series_tmdb_id = ? AND season_number = ? ORDER BY episode_number",
+            (series_id, season_number),
+        )
+    }
+
+    /// Inserts or replaces the season and the full list of its episodes.
+    pub fn upsert_season(
+        &mut self,
+        series_id: tmdb::SeriesId,
+        season: &tmdb::SeasonDetails,
+        update_timestamp: chrono::DateTime<chrono::Utc>,
+    ) -> anyhow::Result<()> {
+        // a savepoint rather than a transaction, so that this can also be part of a bigger transaction
+        let tx = self.conn.savepoint()?;
+
+        tx.execute(
+            "INSERT OR REPLACE INTO seasons (id, series_tmdb_id, season_number, name, air_date, update_timestamp) VALUES (:id, :series_id, :season_number, :name, :air_date, :update_timestamp)",
+            rusqlite::named_params! {
+                ":id": season.id,
+                ":series_id": series_id,
+                ":season_number": season.season_number,
+                ":name": season.name,
+                ":air_date": season.air_date,
+                ":update_timestamp": update_timestamp,
+            },
+        ).with_context(|| format!("Upserting season {} of series {series_id}", season.season_number))?;
+
+        // episodes may have been removed or renumbered, so just replace all of them
+        tx.execute(
+            "DELETE FROM episodes WHERE series_tmdb_id = ? AND season_number = ?",
+            (series_id, season.season_number),
+        )
+        .with_context(|| {
+            format!(
+                "Deleting episodes of season {} of series {series_id}",
+                season.season_number
+            )
+        })?;
+
+        for ep in season.episodes.iter() {
+            tx.execute(
+                "INSERT OR REPLACE INTO episodes (id, series_tmdb_id, season_number, episode_number, name, episode_type, air_date) VALUES (:id, :series_id, :season_number, :episode_number, :name, :episode_type, :air_date)",
+                rusqlite::named_params! {
+                    ":id": ep.id,
+                    ":series_id": series_id,
+                    ":season_number": ep.season_number,
+                    ":episode_number": ep.episode_number,
+                    ":name": ep.name,
+                    ":episode_type": ep.episode_type,
+                    ":air_date": ep.air_date,
+                },
+            ).with_context(|| format!("Inserting episode {} of series {series_id}", ep.identify()))?;
+        }
+
+        tx.commit().with_context(|| {
+            format!(
+                "Committing season {} of series {series_id}",
+                season.season_number
+            )
+        })?;
+        Ok(())
+    }
+
+    pub fn insert_series_history(
+        &mut self,
+        series_id: tmdb::SeriesId,
+        timestamp: chrono::DateTime<chrono::Utc>,
+        changes_json: &serde_json::Value,
+        old_details: &tmdb::SeriesDetails,
+    ) -> anyhow::Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO series_history (series_tmdb_id, timestamp, changes, old_details) VALUES (:series_id, :timestamp, :changes, :old_details)",
+                rusqlite::named_params! {
+                    ":series_id": series_id,
+                    ":timestamp": timestamp,
+                    ":changes": changes_json,
+                    ":old_details": serde_json::to_value(old_details)?,
+                },
+            )
+            .with_context(|| format!("Inserting history of series {series_id}"))?;
+        Ok(())
+    }
+
+    /// All recorded changes of the series, oldest first.
+    pub fn get_series_history(
+        &mut self,
+        series_id: tmdb::SeriesId,
+    ) -> anyhow::Result<Vec<SeriesHistoryEntry>> {
+        self.query_all(
+            "SELECT * FROM series_history WHERE series_tmdb_id = ? ORDER BY timestamp, id",
+            (series_id,),
+        )
+    }
+
+    pub fn get_all_users(&mut self) -> anyhow::Result<Vec<User>> {
+        self.get_all::<User>()
+    }
+
+    pub fn insert_user(&mut self, name: &str, email: &str) -> anyhow::Result<User> {
+        self.conn
+            .execute(
+                "INSERT INTO users (name, email) VALUES (:name, :email)",
+                rusqlite::named_params! {
+                    ":name": name,
+                    ":email": email,
+                },
+            )
+            .with_context(|| format!("Inserting user {name} ({email})"))?;
+
+        Ok(User {
+            id: self.conn.last_insert_rowid(),
+            name: name.to_owned(),
+            email: email.to_owned(),
+        })
+    }
+
+    /// Deletes the user along with all of their subscriptions.
+    pub fn delete_user(&mut self, user: &User) -> anyhow::Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM tracked_series WHERE user_id = ?", (user.id,))
+            .with_context(|| format!("Deleting tracked series of user {user}"))?;
+        tx.execute("DELETE FROM users WHERE id = ?", (user.id,))
+            .with_context(|| format!("Deleting user {user}"))?;
+        tx.commit()
+            .with_context(|| format!("Committing deletion of user {user}"))?;
+        Ok(())
+    }
+
+    /// Subscribes the user to the series. Returns `false` if the user was already subscribed.
+    pub fn insert_tracked_series(
+        &mut self,
+        user_id: i64,
+        series_id: tmdb::SeriesId,
+        start_timestamp: chrono::DateTime<chrono::Utc>,
+    ) -> anyhow::Result<bool> {
+        let inserted_count = self.conn.execute(
+            "INSERT OR IGNORE INTO tracked_series (user_id, series_tmdb_id, start_timestamp) VALUES (:user_id, :series_id, :start_timestamp)",
+            rusqlite::named_params! {
+                ":user_id": user_id,
+                ":series_id": series_id,
+                ":start_timestamp": start_timestamp,
+            }
+        ).with_context(|| format!("Inserting tracked series {series_id} for user {user_id}"))?;
+        Ok(inserted_count > 0)
+    }
+
+    /// Looks up a user by ID, or failing that, by exact name or e-mail address.
+    pub fn find_user(&mut self, id_name_or_email: &str) -> anyhow::Result<Option<User>> {
+        if let Ok(id) = id_name_or_email.parse::<i64>() {
+            return self.get_by_id::<User>(id);
+        }
+
+        let result = self
+            .conn
+            .query_row_and_then(
+                "SELECT * FROM users WHERE name = :key OR email = :key LIMIT 1",
+                rusqlite::named_params! { ":key": id_name_or_email },
+                |row| {
+                    User::from_full_row(row)
+                        .with_context(|| format!("Deserializing user row {row:?}"))
+                },
+            )
+            .with_context(|| format!("Querying user {id_name_or_email:?}"));
+
+        Self::optional_single_row_result(result)
+    }
+
     pub fn get_all_users_subscribed_to_series(
         &mut self,
         series_id: tmdb::SeriesId,
@@ -26,10 +26,12 @@ impl FromSql for PosterId {
 
 #[derive(Debug)]
 pub struct Poster {
+    #[allow(dead_code)]
     pub id: PosterId,
     pub img_data: Box<[u8]>,
     pub mime_type: MimeType,
-    pub source_url: Option<String>, // TODO: shouldn't remain optional once the DB is migrated and we have the source url for everything
+    #[allow(dead_code)]
+    pub source_url: String,
 }
 
 impl TableModel for Poster {
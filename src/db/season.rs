@@ -0,0 +1,33 @@
+use super::table_model::TableModel;
+use crate::tmdb::{self, OptionalDate};
+
+#[derive(Debug)]
+pub struct Season {
+    #[allow(dead_code)]
+    pub id: i32,
+    #[allow(dead_code)]
+    pub series_tmdb_id: tmdb::SeriesId,
+    pub season_number: i32,
+    pub name: String,
+    pub air_date: OptionalDate,
+    #[allow(dead_code)]
+    pub update_timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+impl TableModel for Season {
+    fn table_name() -> &'static str {
+        "seasons"
+    }
+
+    fn from_full_row(row: &rusqlite::Row) -> anyhow::Result<Self> {
+        let result = Self {
+            id: row.get("id")?,
+            series_tmdb_id: row.get("series_tmdb_id")?,
+            season_number: row.get("season_number")?,
+            name: row.get("name")?,
+            air_date: row.get("air_date")?,
+            update_timestamp: row.get("update_timestamp")?,
+        };
+        Ok(result)
+    }
+}
@@ -18,7 +18,12 @@ pub struct Series {
     pub details: tmdb::SeriesDetails,
     pub details_json: serde_json::Value,
 
+    pub external_ids: tmdb::ExternalIds,
+
     pub update_timestamp: chrono::DateTime<chrono::Utc>,
+
+    /// When we found out that the series no longer exists on TMDB, see `relink`.
+    pub orphaned_timestamp: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 impl Series {
@@ -34,6 +39,7 @@ impl Series {
         self.details_json = serde_json::to_value(new_details.clone()).unwrap();
         self.details = new_details;
         self.update_timestamp = update_timestamp;
+        self.orphaned_timestamp = None;
     }
 }
 
@@ -42,6 +48,10 @@ impl TableModel for Series {
         "series"
     }
 
+    fn id_column_name() -> &'static str {
+        "tmdb_id"
+    }
+
     fn from_full_row(row: &rusqlite::Row) -> anyhow::Result<Self> {
         let raw_details = row.get::<_, serde_json::Value>("details")?;
         let result = Self {
@@ -56,7 +66,13 @@ impl TableModel for Series {
             details: serde_json::from_value::<tmdb::SeriesDetails>(raw_details.clone())
                 .context("Deserializing tmdb::SeriesDetails from series.details")?,
             details_json: raw_details,
+            external_ids: tmdb::ExternalIds {
+                imdb_id: row.get("imdb_id")?,
+                tvdb_id: row.get("tvdb_id")?,
+                wikidata_id: row.get("wikidata_id")?,
+            },
             update_timestamp: row.get("update_timestamp")?,
+            orphaned_timestamp: row.get("orphaned_timestamp")?,
         };
         Ok(result)
     }
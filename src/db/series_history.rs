@@ -0,0 +1,33 @@
+use super::table_model::TableModel;
+use crate::tmdb;
+
+/// A set of changes detected when updating a series.
+#[derive(Debug)]
+pub struct SeriesHistoryEntry {
+    #[allow(dead_code)]
+    pub id: i64,
+    #[allow(dead_code)]
+    pub series_tmdb_id: tmdb::SeriesId,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// The serialized `update::SeriesDetailsChanges`.
+    pub changes_json: serde_json::Value,
+    /// The details of the series before the changes.
+    pub old_details_json: serde_json::Value,
+}
+
+impl TableModel for SeriesHistoryEntry {
+    fn table_name() -> &'static str {
+        "series_history"
+    }
+
+    fn from_full_row(row: &rusqlite::Row) -> anyhow::Result<Self> {
+        let result = Self {
+            id: row.get("id")?,
+            series_tmdb_id: row.get("series_tmdb_id")?,
+            timestamp: row.get("timestamp")?,
+            changes_json: row.get("changes")?,
+            old_details_json: row.get("old_details")?,
+        };
+        Ok(result)
+    }
+}
@@ -1,5 +1,11 @@
 pub trait TableModel: Sized {
     fn table_name() -> &'static str;
+
+    /// The name of the primary key column.
+    fn id_column_name() -> &'static str {
+        "id"
+    }
+
     fn from_full_row(row: &rusqlite::Row) -> anyhow::Result<Self>;
 }
 
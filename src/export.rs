@@ -0,0 +1,394 @@
+//! Exporting the users and the series they track to a portable file, and restoring them from it.
+//!
+//! Only what can't be fetched from TMDB again is exported: the users, and for each of them the tracked series
+//! with the timestamp they started tracking it. The title and first air year are included for readability.
+//! The details, seasons and posters of the series are fetched from TMDB again on import.
+//!
+//! The JSON format looks like:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "timestamp": "2024-05-10T06:00:00Z",
+//!   "users": [
+//!     {
+//!       "name": "Alice",
+//!       "email": "alice@example.com",
+//!       "tracked_series": [
+//!         { "tmdb_id": 108545, "title": "3 Body Problem", "first_air_year": 2024, "start_timestamp": "2024-03-22T08:00:00Z" }
+//!       ]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! The CSV format has a row per tracked series with the columns `user_name`, `user_email`, `tmdb_id`, `title`,
+//! `first_air_year` and `start_timestamp`; users who don't track any series have a row with only the user columns.
+
+use anyhow::{Context, bail};
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+
+use crate::{add, cli::ExportFormat};
+
+use super::{AppContext, SeriesId};
+
+const EXPORT_VERSION: u32 = 1;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct TrackedSeriesExport {
+    pub tmdb_id: SeriesId,
+    pub title: String,
+    pub first_air_year: Option<i32>,
+    pub start_timestamp: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct UserExport {
+    pub name: String,
+    pub email: String,
+    pub tracked_series: Vec<TrackedSeriesExport>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Export {
+    pub version: u32,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub users: Vec<UserExport>,
+}
+
+/// A row of the CSV format, see the module docs.
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportRow {
+    user_name: String,
+    user_email: String,
+    tmdb_id: Option<SeriesId>,
+    title: Option<String>,
+    first_air_year: Option<i32>,
+    start_timestamp: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Export {
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        serde_json::to_string_pretty(self).context("Serializing export to JSON")
+    }
+
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let export: Self = serde_json::from_str(json).context("Parsing JSON export")?;
+        if export.version != EXPORT_VERSION {
+            bail!("Unsupported export version: {}", export.version);
+        }
+        Ok(export)
+    }
+
+    pub fn to_csv(&self) -> anyhow::Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for user in self.users.iter() {
+            let mut user_row = ExportRow {
+                user_name: user.name.clone(),
+                user_email: user.email.clone(),
+                tmdb_id: None,
+                title: None,
+                first_air_year: None,
+                start_timestamp: None,
+            };
+            if user.tracked_series.is_empty() {
+                writer.serialize(&user_row)?;
+            }
+            for ts in user.tracked_series.iter() {
+                user_row.tmdb_id = Some(ts.tmdb_id);
+                user_row.title = Some(ts.title.clone());
+                user_row.first_air_year = ts.first_air_year;
+                user_row.start_timestamp = ts.start_timestamp;
+                writer.serialize(&user_row)?;
+            }
+        }
+
+        let csv = writer.into_inner().context("Writing CSV export")?;
+        String::from_utf8(csv).context("Writing CSV export")
+    }
+
+    /// CSV exports don't record when they were made, so that is left as the current time.
+    pub fn from_csv(csv: &str) -> anyhow::Result<Self> {
+        let mut users: Vec<UserExport> = Vec::new();
+        for (row_idx, row) in csv::Reader::from_reader(csv.as_bytes())
+            .deserialize()
+            .enumerate()
+        {
+            let row: ExportRow =
+                row.with_context(|| format!("Parsing CSV export row {row_idx}"))?;
+
+            // rows of the same user are consecutive
+            let user = match users.last_mut() {
+                Some(user) if user.email == row.user_email && user.name == row.user_name => user,
+                _ => {
+                    users.push(UserExport {
+                        name: row.user_name,
+                        email: row.user_email,
+                        tracked_series: Vec::new(),
+                    });
+                    users.last_mut().unwrap()
+                }
+            };
+
+            if let Some(tmdb_id) = row.tmdb_id {
+                user.tracked_series.push(TrackedSeriesExport {
+                    tmdb_id,
+                    title: row.title.unwrap_or_default(),
+                    first_air_year: row.first_air_year,
+                    start_timestamp: row.start_timestamp,
+                });
+            }
+        }
+
+        Ok(Self {
+            version: EXPORT_VERSION,
+            timestamp: chrono::Utc::now(),
+            users,
+        })
+    }
+}
+
+/// Guesses the format from the file extension if not given, defaulting to JSON.
+fn resolve_format(format: Option<ExportFormat>, file_path: &std::path::Path) -> ExportFormat {
+    format.unwrap_or_else(|| match file_path.extension() {
+        Some(ext) if ext.eq_ignore_ascii_case("csv") => ExportFormat::Csv,
+        _ => ExportFormat::Json,
+    })
+}
+
+pub fn collect_export(ctx: &mut AppContext) -> anyhow::Result<Export> {
+    let mut users = Vec::new();
+    for user in ctx.db.get_all_users()? {
+        let tracked_series = ctx
+            .db
+            .get_all_series_tracked_by_user_with_start(user.id)?
+            .into_iter()
+            .map(|(series, start_timestamp)| TrackedSeriesExport {
+                tmdb_id: series.tmdb_id,
+                title: series.title,
+                first_air_year: series.first_air_date.0.map(|dt| dt.year()),
+                start_timestamp,
+            })
+            .collect();
+        users.push(UserExport {
+            name: user.name,
+            email: user.email,
+            tracked_series,
+        });
+    }
+
+    Ok(Export {
+        version: EXPORT_VERSION,
+        timestamp: chrono::Utc::now(),
+        users,
+    })
+}
+
+pub fn export_tracked_series(
+    ctx: &mut AppContext,
+    file_path: &std::path::Path,
+    format: Option<ExportFormat>,
+) -> anyhow::Result<()> {
+    let export = collect_export(ctx)?;
+    let format = resolve_format(format, file_path);
+    let contents = match format {
+        ExportFormat::Json => export.to_json()?,
+        ExportFormat::Csv => export.to_csv()?,
+    };
+
+    std::fs::write(file_path, contents)
+        .with_context(|| format!("Writing export file {file_path:?}"))?;
+    log::info!(
+        "Exported {} user(s) tracking {} series in total to {file_path:?} as {format:?}",
+        export.users.len(),
+        export
+            .users
+            .iter()
+            .map(|u| u.tracked_series.len())
+            .sum::<usize>()
+    );
+    Ok(())
+}
+
+/// Restores the users and the series they track into an empty database, fetching the series from TMDB again.
+/// All series are fetched before anything is stored, so if any of them can't be fetched, nothing is imported.
+pub fn import_export(ctx: &mut AppContext, export: &Export) -> anyhow::Result<()> {
+    let user_count: i64 = ctx
+        .db
+        .conn
+        .query_row("SELECT COUNT(*) FROM users", (), |row| row.get(0))
+        .context("Counting users")?;
+    let series_count: i64 = ctx
+        .db
+        .conn
+        .query_row("SELECT COUNT(*) FROM series", (), |row| row.get(0))
+        .context("Counting series")?;
+    if user_count > 0 || series_count > 0 {
+        bail!(
+            "The database is not empty ({user_count} users, {series_count} series), refusing to import into it"
+        );
+    }
+
+    let mut fetched_ids = Vec::new();
+    let mut new_series = Vec::new();
+    let mut failed_series = Vec::new();
+    for ts in export.users.iter().flat_map(|u| u.tracked_series.iter()) {
+        if fetched_ids.contains(&ts.tmdb_id) || failed_series.contains(&ts.tmdb_id) {
+            continue;
+        }
+
+        log::info!("Fetching series {} ({})", ts.title, ts.tmdb_id);
+        match add::fetch_new_series(ctx, ts.tmdb_id) {
+            Ok(series) => {
+                fetched_ids.push(ts.tmdb_id);
+                new_series.push(series);
+            }
+            Err(err) => {
+                log::error!(
+                    "-- Failed to fetch series {} ({}): {err:?}",
+                    ts.title,
+                    ts.tmdb_id
+                );
+                failed_series.push(ts.tmdb_id);
+            }
+        }
+    }
+
+    if !failed_series.is_empty() {
+        bail!(
+            "Failed to fetch {} series, nothing was imported, see the log: {}",
+            failed_series.len(),
+            failed_series
+                .iter()
+                .map(SeriesId::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    ctx.db.in_transaction(|db| {
+        for series in new_series {
+            add::store_new_series(db, series)?;
+        }
+
+        for exported_user in export.users.iter() {
+            let user = db.insert_user(&exported_user.name, &exported_user.email)?;
+            for ts in exported_user.tracked_series.iter() {
+                let start_timestamp = ts.start_timestamp.unwrap_or_else(chrono::Utc::now);
+                db.insert_tracked_series(user.id, ts.tmdb_id, start_timestamp)?;
+            }
+            log::info!(
+                "Restored user {user} tracking {} series",
+                exported_user.tracked_series.len()
+            );
+        }
+        Ok(())
+    })
+}
+
+pub fn import_tracked_series(
+    ctx: &mut AppContext,
+    file_path: &std::path::Path,
+    format: Option<ExportFormat>,
+) -> anyhow::Result<()> {
+    let contents = std::fs::read_to_string(file_path)
+        .with_context(|| format!("Reading export file {file_path:?}"))?;
+    let export = match resolve_format(format, file_path) {
+        ExportFormat::Json => Export::from_json(&contents),
+        ExportFormat::Csv => Export::from_csv(&contents),
+    }
+    .with_context(|| format!("Reading export file {file_path:?}"))?;
+
+    log::info!(
+        "Importing {} user(s) from {file_path:?}, exported at {}",
+        export.users.len(),
+        export.timestamp
+    );
+    import_export(ctx, &export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn exported_users_and_tracked_series_are_restored_into_a_fresh_database() {
+        let mut ctx = testing::make_context();
+        let alice = testing::insert_user(&mut ctx, "Alice", "alice@example.com");
+        let bob = testing::insert_user(&mut ctx, "Bob", "bob@example.com");
+        testing::insert_user(&mut ctx, "Carol", "carol@example.com");
+        add::add_series_by_id(&mut ctx, SeriesId(108545), &alice).unwrap();
+        add::add_series_by_id(&mut ctx, SeriesId(108545), &bob).unwrap();
+        // not known to TMDB, so it can't be restored
+        testing::insert_series(&mut ctx, testing::make_series_details(1, "Gone"), &[&bob]);
+
+        let export = collect_export(&mut ctx).unwrap();
+        assert_eq!(export.users.len(), 3);
+        assert_eq!(export.users[0].name, "Alice");
+        assert_eq!(
+            export.users[0].tracked_series[0].title,
+            "3 Body Problem".to_owned()
+        );
+        assert_eq!(export.users[0].tracked_series[0].first_air_year, Some(2024));
+        assert_eq!(export.users[1].tracked_series.len(), 2);
+        assert!(export.users[2].tracked_series.is_empty());
+
+        let from_json = Export::from_json(&export.to_json().unwrap()).unwrap();
+        assert_eq!(from_json, export);
+        let from_csv = Export::from_csv(&export.to_csv().unwrap()).unwrap();
+        assert_eq!(from_csv.users, export.users);
+
+        // the series that can't be fetched makes the whole import fail without leaving anything behind
+        let mut restored = testing::make_context();
+        let err = import_export(&mut restored, &from_csv).unwrap_err();
+        assert!(err.to_string().contains("Failed to fetch 1 series"));
+        assert_eq!(testing::count_rows(&mut restored, "users"), 0);
+        assert_eq!(testing::count_rows(&mut restored, "series"), 0);
+
+        let mut from_csv = from_csv;
+        from_csv.users[1]
+            .tracked_series
+            .retain(|ts| ts.tmdb_id != SeriesId(1));
+
+        // as does failing to store anything
+        testing::inject_failure(&mut restored, "INSERT", "tracked_series");
+        assert!(import_export(&mut restored, &from_csv).is_err());
+        assert_eq!(testing::count_rows(&mut restored, "users"), 0);
+        assert_eq!(testing::count_rows(&mut restored, "series"), 0);
+        restored
+            .db
+            .conn
+            .execute_batch("DROP TRIGGER fail_INSERT_tracked_series")
+            .unwrap();
+
+        import_export(&mut restored, &from_csv).unwrap();
+
+        let users = restored.db.get_all_users().unwrap();
+        assert_eq!(
+            users.iter().map(|u| u.email.as_str()).collect::<Vec<_>>(),
+            ["alice@example.com", "bob@example.com", "carol@example.com"]
+        );
+        let series = restored
+            .db
+            .get_series_by_id(SeriesId(108545))
+            .unwrap()
+            .unwrap();
+        assert_eq!(series.title, "3 Body Problem");
+        assert!(restored.db.get_series_by_id(SeriesId(1)).unwrap().is_none());
+        for user in users.iter().take(2) {
+            let tracked = restored.db.get_all_series_tracked_by_user(user.id).unwrap();
+            assert_eq!(tracked.len(), 1);
+        }
+
+        let reexport = collect_export(&mut restored).unwrap();
+        assert_eq!(
+            reexport.users[0].tracked_series,
+            export.users[0].tracked_series
+        );
+
+        // importing again would duplicate the users
+        assert!(import_export(&mut restored, &from_json).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::{Context, bail};
+
+use crate::{db, tmdb, update::SeriesDetailsChanges};
+
+use super::AppContext;
+
+/// A set of changes of a series, along with its details before the changes.
+#[derive(Debug)]
+pub struct HistoryEntry {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub changes: SeriesDetailsChanges,
+    #[allow(dead_code)]
+    pub old_details: tmdb::SeriesDetails,
+}
+
+/// All recorded changes of the series, oldest first.
+pub fn collect_history(
+    ctx: &mut AppContext,
+    series: &db::Series,
+) -> anyhow::Result<Vec<HistoryEntry>> {
+    let mut history = Vec::new();
+    for entry in ctx.db.get_series_history(series.tmdb_id)? {
+        history.push(HistoryEntry {
+            timestamp: entry.timestamp,
+            changes: serde_json::from_value(entry.changes_json).with_context(|| {
+                format!(
+                    "Deserializing changes of series {} at {}",
+                    series.details, entry.timestamp
+                )
+            })?,
+            old_details: serde_json::from_value(entry.old_details_json).with_context(|| {
+                format!(
+                    "Deserializing old details of series {} at {}",
+                    series.details, entry.timestamp
+                )
+            })?,
+        });
+    }
+    Ok(history)
+}
+
+pub fn print_history(ctx: &mut AppContext, id_or_title: &str) -> anyhow::Result<()> {
+    let Some(series) = ctx.db.find_series(id_or_title)? else {
+        bail!("No tracked series with ID or title {id_or_title:?}")
+    };
+
+    let history = collect_history(ctx, &series)?;
+
+    println!("{}", series.details);
+    for entry in history.iter() {
+        print!("{}:{}", entry.timestamp, entry.changes.summary());
+    }
+    println!("{} change(s)", history.len());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing, update};
+
+    #[test]
+    fn updates_with_changes_are_recorded_in_the_history() {
+        let mut ctx = testing::make_context();
+        let old_details = testing::make_outdated_series_details(&mut ctx, tmdb::SeriesId(108545));
+        let mut series = testing::insert_series(&mut ctx, old_details, &[]);
+        let mut orphan = testing::insert_series(
+            &mut ctx,
+            testing::make_series_details(1, "Deleted Series"),
+            &[],
+        );
+
+        // dry runs leave no trace
+        update::update_one_series(&mut ctx, &mut series, true, true).unwrap();
+        assert!(collect_history(&mut ctx, &series).unwrap().is_empty());
+        let mut series = ctx.db.get_series_by_id(series.tmdb_id).unwrap().unwrap();
+
+        update::update_one_series(&mut ctx, &mut series, true, false).unwrap();
+        // nothing changes the second time around, so there is nothing to record
+        update::update_one_series(&mut ctx, &mut series, true, false).unwrap();
+        update::update_one_series(&mut ctx, &mut orphan, true, false).unwrap();
+
+        let history = collect_history(&mut ctx, &series).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].timestamp, series.update_timestamp);
+        assert_eq!(history[0].changes.episode_count_change, Some((5, 8)));
+        assert_eq!(history[0].changes.aired_episodes.len(), 3);
+        assert_eq!(history[0].old_details.number_of_episodes, 5);
+
+        let history = collect_history(&mut ctx, &orphan).unwrap();
+        assert_eq!(history.len(), 1);
+        assert!(history[0].changes.orphaned);
+        assert_eq!(history[0].old_details.name, "Deleted Series");
+    }
+}
@@ -0,0 +1,214 @@
+use anyhow::Context;
+use chrono::Days;
+
+use crate::{db, tmdb};
+
+use super::{AppContext, EpisodeDetails};
+
+/// An all-day calendar event for the airing of an episode.
+#[derive(Debug)]
+pub struct EpisodeEvent {
+    pub series_id: tmdb::SeriesId,
+    pub series_title: String,
+    pub series_url: String,
+    pub external_ids: tmdb::ExternalIds,
+    pub episode: EpisodeDetails,
+}
+
+impl EpisodeEvent {
+    /// Stable across exports, so that calendar apps update existing events instead of duplicating them.
+    pub fn uid(&self) -> String {
+        format!("episode-{}@tvtrack", self.episode.id)
+    }
+}
+
+/// Escapes a TEXT value, see RFC 5545 section 3.3.11.
+fn escape_text(text: &str) -> String {
+    text.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Appends a content line, folding it so that no line is longer than 75 octets, see RFC 5545 section 3.1.
+fn push_line(ics: &mut String, line: &str) {
+    let mut line_len = 0;
+    for c in line.chars() {
+        if line_len + c.len_utf8() > 75 {
+            *ics += "\r\n ";
+            line_len = 1;
+        }
+        ics.push(c);
+        line_len += c.len_utf8();
+    }
+    *ics += "\r\n";
+}
+
+pub fn make_calendar(events: &[EpisodeEvent], timestamp: chrono::DateTime<chrono::Utc>) -> String {
+    let mut ics = String::new();
+    push_line(&mut ics, "BEGIN:VCALENDAR");
+    push_line(&mut ics, "VERSION:2.0");
+    push_line(&mut ics, "PRODID:-//tvtrack//tvtrack//EN");
+    push_line(&mut ics, "CALSCALE:GREGORIAN");
+    push_line(&mut ics, "X-WR-CALNAME:TVTrack");
+
+    let dtstamp = timestamp.format("%Y%m%dT%H%M%SZ");
+    for event in events.iter() {
+        let Some(air_date) = event.episode.air_date.0 else {
+            continue;
+        };
+        let ep = &event.episode;
+
+        push_line(&mut ics, "BEGIN:VEVENT");
+        push_line(&mut ics, &format!("UID:{}", event.uid()));
+        push_line(&mut ics, &format!("DTSTAMP:{dtstamp}"));
+        push_line(
+            &mut ics,
+            &format!("DTSTART;VALUE=DATE:{}", air_date.format("%Y%m%d")),
+        );
+        push_line(
+            &mut ics,
+            &format!(
+                "DTEND;VALUE=DATE:{}",
+                (air_date + Days::new(1)).format("%Y%m%d")
+            ),
+        );
+        push_line(
+            &mut ics,
+            &format!(
+                "SUMMARY:{}",
+                escape_text(&format!(
+                    "{} S{:02}E{:02} {}",
+                    event.series_title, ep.season_number, ep.episode_number, ep.name
+                ))
+            ),
+        );
+        let mut description = format!("{}\nTMDB: {}", ep.identify(), event.series_url);
+        for (site, url) in event.external_ids.links() {
+            description += &format!("\n{site}: {url}");
+        }
+        push_line(
+            &mut ics,
+            &format!("DESCRIPTION:{}", escape_text(&description)),
+        );
+        push_line(&mut ics, &format!("URL:{}", event.series_url));
+        push_line(&mut ics, "TRANSP:TRANSPARENT");
+        push_line(&mut ics, "END:VEVENT");
+    }
+
+    push_line(&mut ics, "END:VCALENDAR");
+    ics
+}
+
+/// All known episodes of the series airing on or after `from_date`: the next episode to air,
+/// as well as any episodes from the stored season data.
+pub fn collect_upcoming_episodes(
+    ctx: &mut AppContext,
+    series: &db::Series,
+    from_date: chrono::NaiveDate,
+) -> anyhow::Result<Vec<EpisodeEvent>> {
+    let mut episodes: Vec<EpisodeDetails> = ctx
+        .db
+        .get_episodes_of_series(series.tmdb_id)?
+        .iter()
+        .map(db::Episode::to_details)
+        .collect();
+
+    if let Some(next_ep) = &series.details.next_episode_to_air {
+        if episodes.iter().all(|ep| ep.id != next_ep.id) {
+            episodes.push(next_ep.clone());
+        }
+    }
+
+    episodes.retain(|ep| ep.air_date.is_some_and(|dt| dt >= from_date));
+    episodes.sort_by_key(|ep| (ep.air_date, ep.season_number, ep.episode_number));
+
+    let series_url = ctx.tmdb.make_series_url(series.tmdb_id);
+    let events = episodes
+        .into_iter()
+        .map(|episode| EpisodeEvent {
+            series_id: series.tmdb_id,
+            series_title: series.title.clone(),
+            series_url: series_url.clone(),
+            external_ids: series.external_ids.clone(),
+            episode,
+        })
+        .collect();
+    Ok(events)
+}
+
+pub fn export_ical(
+    ctx: &mut AppContext,
+    file_path: &std::path::Path,
+    user: Option<&db::User>,
+) -> anyhow::Result<()> {
+    let series = match user {
+        None => ctx.db.get_all_series()?,
+        Some(user) => ctx.db.get_all_series_tracked_by_user(user.id)?,
+    };
+
+    let now = chrono::Utc::now();
+    let mut events = Vec::new();
+    for s in series.iter() {
+        events.extend(collect_upcoming_episodes(ctx, s, now.date_naive())?);
+    }
+    events.sort_by_key(|e| (e.episode.air_date, e.series_id));
+
+    std::fs::write(file_path, make_calendar(&events, now))
+        .with_context(|| format!("Writing iCalendar file {file_path:?}"))?;
+    log::info!(
+        "Exported {} upcoming episodes of {} series to {file_path:?}",
+        events.len(),
+        series.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn calendar_has_one_all_day_event_per_episode_with_stable_uid() {
+        let mut episode = testing::make_episode(5051968, 1, 8, "2024-03-21");
+        episode.name = "Wallfacer, part; one".to_owned();
+        let events = [EpisodeEvent {
+            series_id: tmdb::SeriesId(108545),
+            series_title: "3 Body Problem".to_owned(),
+            series_url: "https://www.themoviedb.org/tv/108545".to_owned(),
+            external_ids: tmdb::ExternalIds {
+                imdb_id: Some("tt13016388".to_owned()),
+                ..Default::default()
+            },
+            episode,
+        }];
+        let timestamp = "2024-03-01T12:00:00Z".parse().unwrap();
+
+        let ics = make_calendar(&events, timestamp);
+        let lines: Vec<_> = ics.split("\r\n").collect();
+
+        assert!(lines.iter().all(|line| line.len() <= 75));
+        assert_eq!(lines.first(), Some(&"BEGIN:VCALENDAR"));
+        assert_eq!(lines.iter().filter(|l| **l == "BEGIN:VEVENT").count(), 1);
+        assert!(lines.contains(&"UID:episode-5051968@tvtrack"));
+        assert!(lines.contains(&"DTSTAMP:20240301T120000Z"));
+        assert!(lines.contains(&"DTSTART;VALUE=DATE:20240321"));
+        assert!(lines.contains(&"DTEND;VALUE=DATE:20240322"));
+        assert!(lines.contains(&"SUMMARY:3 Body Problem S01E08 Wallfacer\\, part\\; one"));
+        // long lines are folded, so unfold them before looking for the links
+        let unfolded = ics.replace("\r\n ", "");
+        assert!(unfolded.contains("\\nIMDb: https://www.imdb.com/title/tt13016388/"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+    }
+
+    #[test]
+    fn carriage_returns_are_escaped_as_newlines() {
+        assert_eq!(
+            escape_text("one\r\ntwo\rthree\nfour"),
+            "one\\ntwo\\nthree\\nfour"
+        );
+    }
+}
@@ -0,0 +1,355 @@
+//! Importing the series from the exports of other trackers:
+//!
+//! - Trakt JSON backups of the watchlist, watched or collected shows: an array of items with a `show` object holding
+//!   the `title`, `year` and `ids` (`tmdb`, `imdb`, `tvdb`); items that are not shows (e.g. movies) are skipped.
+//! - TV Time CSV exports of the followed shows, with the `tv_show_name` and `tv_show_id` (a TVDB ID) columns.
+//! - IMDb list (or watchlist) CSV exports, with the `Const` (IMDb ID), `Title`, `Title Type` and `Year` columns;
+//!   titles that are not TV series (e.g. movies) are skipped.
+//!
+//! Entries are mapped to TMDB IDs directly if they have one, by their external IDs if not, and by searching for
+//! their title as a last resort. They are then added like the lines of `add-from`.
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::{
+    add::{self, CandidateMatch, ImportSummary},
+    cli::{ImportFormat, PickStrategy},
+    db,
+    tmdb::{self, SeriesId},
+};
+
+use super::AppContext;
+
+/// A series found in an export.
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportEntry {
+    pub title: String,
+    pub first_air_year: Option<i32>,
+    pub tmdb_id: Option<SeriesId>,
+    pub imdb_id: Option<String>,
+    pub tvdb_id: Option<i64>,
+}
+
+impl ImportEntry {
+    /// The entry as a line in the format `add-from` accepts, preferring external IDs over the title.
+    pub fn to_line(&self) -> String {
+        match self.external_ids().first() {
+            Some((source, external_id)) => external_id_line(*source, external_id),
+            None => self.title_line(),
+        }
+    }
+
+    /// The title of the entry as a line in the format `add-from` accepts.
+    pub fn title_line(&self) -> String {
+        match self.first_air_year {
+            Some(year) => format!("{} ({year})", self.title),
+            None => self.title.clone(),
+        }
+    }
+
+    fn external_ids(&self) -> Vec<(tmdb::ExternalSource, String)> {
+        let mut ids = Vec::new();
+        if let Some(imdb_id) = &self.imdb_id {
+            ids.push((tmdb::ExternalSource::Imdb, imdb_id.clone()));
+        }
+        if let Some(tvdb_id) = self.tvdb_id {
+            ids.push((tmdb::ExternalSource::Tvdb, tvdb_id.to_string()));
+        }
+        ids
+    }
+}
+
+fn external_id_line(source: tmdb::ExternalSource, external_id: &str) -> String {
+    format!("{source}:{external_id}")
+}
+
+#[derive(Debug, Deserialize)]
+struct TraktItem {
+    show: Option<TraktShow>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TraktShow {
+    title: String,
+    year: Option<i32>,
+    #[serde(default)]
+    ids: TraktIds,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TraktIds {
+    tmdb: Option<i32>,
+    imdb: Option<String>,
+    tvdb: Option<i64>,
+}
+
+pub fn parse_trakt(json: &str) -> anyhow::Result<Vec<ImportEntry>> {
+    let items: Vec<TraktItem> = serde_json::from_str(json).context("Parsing Trakt JSON")?;
+
+    Ok(items
+        .into_iter()
+        .filter_map(|item| item.show)
+        .map(|show| ImportEntry {
+            title: show.title,
+            first_air_year: show.year,
+            tmdb_id: show.ids.tmdb.map(SeriesId),
+            imdb_id: show.ids.imdb,
+            tvdb_id: show.ids.tvdb,
+        })
+        .collect())
+}
+
+#[derive(Debug, Deserialize)]
+struct TvTimeRecord {
+    tv_show_name: String,
+    tv_show_id: Option<i64>,
+}
+
+pub fn parse_tv_time(csv: &str) -> anyhow::Result<Vec<ImportEntry>> {
+    let mut entries = Vec::new();
+    for record in csv::Reader::from_reader(csv.as_bytes()).deserialize() {
+        let record: TvTimeRecord = record.context("Parsing TV Time CSV")?;
+        entries.push(ImportEntry {
+            title: record.tv_show_name,
+            tvdb_id: record.tv_show_id,
+            ..Default::default()
+        });
+    }
+    Ok(entries)
+}
+
+#[derive(Debug, Deserialize)]
+struct ImdbListRecord {
+    #[serde(rename = "Const")]
+    id: String,
+    #[serde(rename = "Title")]
+    title: String,
+    #[serde(rename = "Title Type")]
+    title_type: String,
+    #[serde(rename = "Year")]
+    year: Option<i32>,
+}
+
+impl ImdbListRecord {
+    /// Older exports use e.g. "TV Mini Series", newer ones "tvMiniSeries".
+    fn is_series(&self) -> bool {
+        let title_type = self.title_type.replace(' ', "").to_lowercase();
+        title_type == "tvseries" || title_type == "tvminiseries"
+    }
+}
+
+pub fn parse_imdb_list(csv: &str) -> anyhow::Result<Vec<ImportEntry>> {
+    let mut entries = Vec::new();
+    for record in csv::Reader::from_reader(csv.as_bytes()).deserialize() {
+        let record: ImdbListRecord = record.context("Parsing IMDb list CSV")?;
+        if !record.is_series() {
+            log::debug!(
+                "Skipping {} ({}): {}",
+                record.title,
+                record.title_type,
+                record.id
+            );
+            continue;
+        }
+        entries.push(ImportEntry {
+            title: record.title,
+            first_air_year: record.year,
+            imdb_id: Some(record.id),
+            ..Default::default()
+        });
+    }
+    Ok(entries)
+}
+
+/// Tries the TMDB ID, the external IDs and the title of the entry in turn, until one of them matches.
+/// Returns the outcome along with the line (see `ImportEntry::to_line`) of what was last tried,
+/// which is the line of the first failed lookup if none matched and any of them failed.
+fn match_entry(
+    ctx: &mut AppContext,
+    entry: &ImportEntry,
+    pick: Option<PickStrategy>,
+) -> (String, anyhow::Result<CandidateMatch>) {
+    if let Some(id) = entry.tmdb_id {
+        return (entry.to_line(), Ok(CandidateMatch::Found(id)));
+    }
+
+    // a failed lookup is no reason not to try the others
+    let mut first_failure = None;
+    for (source, external_id) in entry.external_ids() {
+        let line = external_id_line(source, &external_id);
+        match add::match_external_id(ctx, source, &external_id, pick) {
+            Ok(CandidateMatch::NotFound) => {}
+            Ok(matched) => return (line, Ok(matched)),
+            Err(err) => {
+                log::warn!("-- Failed to look up {line}: {err:#}");
+                first_failure.get_or_insert((line, err));
+            }
+        }
+    }
+
+    let line = entry.title_line();
+    match add::match_title(ctx, &entry.title, entry.first_air_year, pick) {
+        Ok(CandidateMatch::NotFound) => match first_failure {
+            Some((line, err)) => (line, Err(err)),
+            None => (line, Ok(CandidateMatch::NotFound)),
+        },
+        matched => (line, matched),
+    }
+}
+
+/// Adds all series found in the export. Entries that can't be added don't stop the rest from being added,
+/// they are collected in the returned summary, and written to `unresolved_path` if given.
+pub fn import_series(
+    ctx: &mut AppContext,
+    format: ImportFormat,
+    file_path: &std::path::Path,
+    pick: Option<PickStrategy>,
+    unresolved_path: Option<&std::path::Path>,
+    user: &db::User,
+) -> anyhow::Result<ImportSummary> {
+    log::info!("Importing series from {format:?} export: {file_path:?}");
+
+    let contents = std::fs::read_to_string(file_path)
+        .with_context(|| format!("Reading export file {file_path:?}"))?;
+    let entries = match format {
+        ImportFormat::Trakt => parse_trakt(&contents),
+        ImportFormat::TvTime => parse_tv_time(&contents),
+        ImportFormat::ImdbList => parse_imdb_list(&contents),
+    }
+    .with_context(|| format!("Reading export file {file_path:?}"))?;
+
+    let mut summary = ImportSummary::default();
+    for entry in entries.iter() {
+        log::info!("Importing: {}", entry.to_line());
+        let (line, matched) = match_entry(ctx, entry, pick);
+        summary.add_match(ctx, &line, matched, user);
+    }
+
+    log::info!("Done importing series from {file_path:?}: {summary}");
+
+    if let Some(unresolved_path) = unresolved_path {
+        summary.write_unresolved(unresolved_path)?;
+        log::info!(
+            "Wrote {} unresolved line(s) to {unresolved_path:?}",
+            summary.unresolved_count()
+        );
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn parses_trakt_tv_time_and_imdb_list_exports() {
+        let trakt = r#"[
+            { "listed_at": "2024-04-01T10:00:00.000Z", "type": "show",
+              "show": { "title": "3 Body Problem", "year": 2024,
+                        "ids": { "trakt": 185685, "slug": "3-body-problem", "tvdb": 411384, "imdb": "tt13016388", "tmdb": 108545 } } },
+            { "listed_at": "2024-04-02T10:00:00.000Z", "type": "movie",
+              "movie": { "title": "Dune: Part Two", "year": 2024, "ids": { "tmdb": 693134 } } },
+            { "type": "show", "show": { "title": "Elsbeth", "year": null, "ids": { "imdb": null } } }
+        ]"#;
+        assert_eq!(
+            parse_trakt(trakt).unwrap(),
+            [
+                ImportEntry {
+                    title: "3 Body Problem".to_owned(),
+                    first_air_year: Some(2024),
+                    tmdb_id: Some(SeriesId(108545)),
+                    imdb_id: Some("tt13016388".to_owned()),
+                    tvdb_id: Some(411384),
+                },
+                ImportEntry {
+                    title: "Elsbeth".to_owned(),
+                    ..Default::default()
+                },
+            ]
+        );
+
+        let tv_time = "tv_show_id,tv_show_name,created_at\n\
+            411384,3 Body Problem,2024-03-22 08:00:00\n\
+            ,\"Elsbeth, the show\",2024-03-01 08:00:00\n";
+        let entries = parse_tv_time(tv_time).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].tvdb_id, Some(411384));
+        assert_eq!(entries[0].to_line(), "tvdb:411384");
+        assert_eq!(entries[1].tvdb_id, None);
+        assert_eq!(entries[1].to_line(), "Elsbeth, the show");
+
+        let imdb_list = "Position,Const,Created,Modified,Description,Title,URL,Title Type,IMDb Rating,Runtime (mins),Year,Genres,Num Votes,Release Date,Directors\n\
+            1,tt13016388,2024-04-01,2024-04-01,,3 Body Problem,https://www.imdb.com/title/tt13016388/,TV Series,7.5,60,2024,\"Adventure, Drama\",100000,2024-03-21,\n\
+            2,tt15239678,2024-04-01,2024-04-01,,Dune: Part Two,https://www.imdb.com/title/tt15239678/,Movie,8.5,166,2024,Action,500000,2024-02-27,Denis Villeneuve\n\
+            3,tt2788316,2024-04-01,2024-04-01,,Shogun,https://www.imdb.com/title/tt2788316/,tvMiniSeries,8.6,59,,Drama,200000,2024-02-27,\n";
+        let entries = parse_imdb_list(imdb_list).unwrap();
+        assert_eq!(
+            entries.iter().map(ImportEntry::to_line).collect::<Vec<_>>(),
+            ["imdb:tt13016388", "imdb:tt2788316"]
+        );
+        assert_eq!(entries[0].first_air_year, Some(2024));
+        assert_eq!(entries[1].first_air_year, None);
+    }
+
+    #[test]
+    fn imports_by_tmdb_id_external_id_or_title() {
+        let mut ctx = testing::make_context();
+        let alice = testing::insert_user(&mut ctx, "Alice", "alice@example.com");
+
+        let found = ctx
+            .tmdb
+            .find_by_external_id(tmdb::ExternalSource::Imdb, "tt13016388")
+            .unwrap();
+        ctx.tmdb = Box::new(tmdb::FixtureClient::new().with_find_results(
+            tmdb::ExternalSource::Tvdb,
+            "411384",
+            found,
+        ));
+
+        let dir = std::env::temp_dir().join(format!("tvtrack-import-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file_path = dir.join("watchlist.json");
+        std::fs::write(
+            &file_path,
+            r#"[
+                { "show": { "title": "3 Body Problem", "year": 2024, "ids": { "imdb": "tt13016388" } } },
+                { "show": { "title": "Elsbeth", "year": 2024, "ids": { "imdb": "tt0000000" } } },
+                { "show": { "title": "3 Body Problem", "year": 2024, "ids": { "tmdb": 108545 } } },
+                { "show": { "title": "No Such Series", "year": 2020, "ids": {} } },
+                { "show": { "title": "3 Body Problem", "year": 2024, "ids": { "imdb": "bogus", "tvdb": 411384 } } },
+                { "show": { "title": "Gone Series", "year": 2020, "ids": { "imdb": "bogus" } } }
+            ]"#,
+        )
+        .unwrap();
+
+        let summary = import_series(
+            &mut ctx,
+            ImportFormat::Trakt,
+            &file_path,
+            None,
+            None,
+            &alice,
+        )
+        .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Elsbeth isn't known by its IMDb ID, so it's found by its title instead,
+        // but adding it fails as there are no season details for it
+        assert_eq!(summary.added, 1);
+        // a failed lookup of an external ID doesn't stop the others from being tried
+        assert_eq!(summary.already_tracked, 2);
+        assert_eq!(summary.not_found, ["No Such Series (2020)"]);
+        assert!(summary.ambiguous.is_empty());
+        // the lines recorded are the ones that were tried last, or failed if nothing else was found
+        let errored: Vec<_> = summary.errored.iter().map(|(line, _)| line).collect();
+        assert_eq!(errored, ["Elsbeth (2024)", "imdb:bogus"]);
+        assert!(summary.errored[1].1.contains("Invalid imdb ID"));
+
+        let tracked = ctx.db.get_all_series_tracked_by_user(alice.id).unwrap();
+        assert_eq!(tracked.len(), 1);
+        assert_eq!(tracked[0].tmdb_id, SeriesId(108545));
+    }
+}
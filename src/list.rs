@@ -0,0 +1,112 @@
+use anyhow::bail;
+
+use crate::{cli::ListSortKey, db, update};
+
+use super::{AppContext, EpisodeDetails, SeriesStatus};
+
+pub fn list_series(
+    ctx: &mut AppContext,
+    status: Option<SeriesStatus>,
+    user: Option<&str>,
+    sort: ListSortKey,
+    with_episodes: bool,
+) -> anyhow::Result<()> {
+    let mut series = match user {
+        None => ctx.db.get_all_series()?,
+        Some(user) => {
+            let Some(user) = ctx.db.find_user(user)? else {
+                bail!("No such user: {user}")
+            };
+            ctx.db.get_all_series_tracked_by_user(user.id)?
+        }
+    };
+
+    if let Some(status) = status {
+        series.retain(|s| s.status == status);
+    }
+
+    match sort {
+        ListSortKey::Title => series.sort_by(|a, b| a.title.cmp(&b.title)),
+        ListSortKey::FirstAirDate => series.sort_by_key(|s| s.first_air_date),
+        // series with an unknown next episode go last
+        ListSortKey::NextEpisode => {
+            series.sort_by_key(|s| (s.next_episode_air_date.is_none(), s.next_episode_air_date))
+        }
+        ListSortKey::LastUpdate => series.sort_by_key(|s| s.update_timestamp),
+        ListSortKey::NextUpdate => {
+            series.sort_by_key(|s| update::determine_next_update_timestamp(s).0)
+        }
+    }
+
+    for s in series.iter() {
+        print_series(s);
+        if with_episodes {
+            print_episodes(ctx, s)?;
+        }
+    }
+    println!("{} series", series.len());
+
+    Ok(())
+}
+
+fn print_series(series: &db::Series) {
+    let (next_update_timestamp, next_update_reason) =
+        update::determine_next_update_timestamp(series);
+
+    println!(
+        "{} | {} | {}",
+        series.details,
+        series.status,
+        if series.in_production {
+            "in production"
+        } else {
+            "not in production"
+        }
+    );
+    println!(
+        "    Last episode: {}",
+        series
+            .details
+            .last_episode_to_air
+            .as_ref()
+            .map(EpisodeDetails::identify)
+            .unwrap_or("none".to_owned())
+    );
+    println!(
+        "    Next episode: {}",
+        series
+            .details
+            .next_episode_to_air
+            .as_ref()
+            .map(EpisodeDetails::identify)
+            .unwrap_or("unknown".to_owned())
+    );
+    println!(
+        "    Last update: {} | next update: {next_update_timestamp} ({next_update_reason})",
+        series.update_timestamp
+    );
+    if let Some(orphaned_timestamp) = series.orphaned_timestamp {
+        println!(
+            "    Orphaned: no longer exists on TMDB since {orphaned_timestamp}; use `relink` to link it to another TMDB series"
+        );
+    }
+}
+
+fn print_episodes(ctx: &mut AppContext, series: &db::Series) -> anyhow::Result<()> {
+    let seasons = ctx.db.get_seasons_of_series(series.tmdb_id)?;
+    let episodes = ctx.db.get_episodes_of_series(series.tmdb_id)?;
+
+    for season in seasons.iter() {
+        println!(
+            "    Season {}: {} (from {})",
+            season.season_number, season.name, season.air_date
+        );
+        for ep in episodes
+            .iter()
+            .filter(|ep| ep.season_number == season.season_number)
+        {
+            println!("        {}", ep.to_details().identify());
+        }
+    }
+    Ok(())
+}
@@ -1,11 +1,24 @@
 mod add;
+mod backup;
 mod cli;
 mod config;
 mod context;
 mod db;
+mod export;
+mod history;
+mod ical;
+mod importer;
+mod list;
 mod notify;
+mod relink;
+mod remove;
+mod search;
+mod subscribe;
+#[cfg(test)]
+mod testing;
 mod tmdb;
 mod update;
+mod user;
 
 use std::path::PathBuf;
 
@@ -42,13 +55,15 @@ fn main() -> anyhow::Result<()> {
     };
 
     let mut ctx = {
-        let db = Db::open(&config.state_file_path.0)?;
+        // a dry run must leave the database untouched, so it's not even created or migrated
+        let db = match &args.command {
+            cli::Command::Update { dry_run: true, .. } => {
+                Db::open_read_only(&config.state_file_path.0)?
+            }
+            _ => Db::open(&config.state_file_path.0)?,
+        };
 
-        // TODO: this is a bit ugly, just pass &config.tmdb instead?
-        let tmdb_client = tmdb::Client::new(
-            config.tmdb.api_key.clone(),
-            config.tmdb.api_access_token.clone(),
-        );
+        let tmdb_client = Box::new(tmdb::Client::new(&config.tmdb));
 
         AppContext {
             config,
@@ -57,21 +72,74 @@ fn main() -> anyhow::Result<()> {
         }
     };
 
+    if let Some(backup_name) = args.command.backup_name() {
+        backup::backup_before(&mut ctx, backup_name)?;
+    }
+
     match &args.command {
         cli::Command::AddByTitle {
             title,
             first_air_year,
+            interactive,
+            pick,
+            user,
         } => {
-            add::add_series_by_title(&mut ctx, title, *first_air_year)?;
+            let user = user::resolve_user(&mut ctx, user.as_deref())?;
+            add::add_series_by_title(&mut ctx, title, *first_air_year, *pick, *interactive, &user)?;
         }
-        cli::Command::AddById { tmdb_id } => {
-            add::add_series_by_id(&mut ctx, SeriesId(*tmdb_id))?;
+        cli::Command::AddById { tmdb_id, user } => {
+            let user = user::resolve_user(&mut ctx, user.as_deref())?;
+            add::add_series_by_id(&mut ctx, SeriesId(*tmdb_id), &user)?;
         }
-        cli::Command::AddFrom { file_path } => {
-            add::multi_add_series_from_file(&mut ctx, file_path)?;
+        cli::Command::AddByExternalId {
+            external_id,
+            source,
+            user,
+        } => {
+            let user = user::resolve_user(&mut ctx, user.as_deref())?;
+            add::add_series_by_external_id(&mut ctx, *source, external_id, &user)?;
+        }
+        cli::Command::AddFrom {
+            file_path,
+            pick,
+            unresolved,
+            user,
+        } => {
+            let user = user::resolve_user(&mut ctx, user.as_deref())?;
+            let summary = add::multi_add_series_from_file(
+                &mut ctx,
+                file_path,
+                *pick,
+                unresolved.as_deref(),
+                &user,
+            )?;
+            println!("{summary}");
+        }
+        cli::Command::ImportFrom {
+            format,
+            file_path,
+            pick,
+            unresolved,
+            user,
+        } => {
+            let user = user::resolve_user(&mut ctx, user.as_deref())?;
+            let summary = importer::import_series(
+                &mut ctx,
+                *format,
+                file_path,
+                *pick,
+                unresolved.as_deref(),
+                &user,
+            )?;
+            println!("{summary}");
         }
-        cli::Command::Update { tmdb_id, force } => {
+        cli::Command::Update {
+            tmdb_id,
+            force,
+            dry_run,
+        } => {
             let force = force.unwrap_or(false);
+            let dry_run = *dry_run;
 
             let all_series_changes = if let Some(series_id) = tmdb_id {
                 let series_id = SeriesId(*series_id);
@@ -80,18 +148,89 @@ fn main() -> anyhow::Result<()> {
                     bail!("No tracked series with ID {series_id}")
                 };
 
-                match update::update_one_series(&mut ctx, &mut series, force)? {
+                match update::update_one_series(&mut ctx, &mut series, force, dry_run)? {
                     None => vec![],
                     Some(changes) => vec![(series, changes)],
                 }
             } else {
-                update::update_all_series(&mut ctx, force)?
+                update::update_all_series(&mut ctx, force, dry_run)?
             };
 
+            if dry_run {
+                for (series, changes) in all_series_changes.iter() {
+                    println!("Would update {}: {}", series.details, changes.summary());
+                }
+            }
+
             if !all_series_changes.is_empty() {
-                notify::send_email_notifications(&mut ctx, &all_series_changes)?;
+                notify::send_notifications(&mut ctx, &all_series_changes, dry_run)?;
             }
         }
+        cli::Command::Remove {
+            series,
+            user,
+            purge,
+        } => {
+            let user = user::resolve_user(&mut ctx, user.as_deref())?;
+            remove::remove_series_by_id_or_title(&mut ctx, series, &user, *purge)?;
+        }
+        cli::Command::Subscribe { series, user } => {
+            let user = user::resolve_user(&mut ctx, Some(user))?;
+            subscribe::subscribe(&mut ctx, series, &user)?;
+        }
+        cli::Command::Unsubscribe { series, user } => {
+            let user = user::resolve_user(&mut ctx, Some(user))?;
+            subscribe::unsubscribe(&mut ctx, series, &user)?;
+        }
+        cli::Command::Search {
+            title,
+            first_air_year,
+            max_pages,
+            json,
+        } => {
+            search::search_series(&mut ctx, title, *first_air_year, *max_pages, *json)?;
+        }
+        cli::Command::Relink { series, tmdb_id } => {
+            relink::relink_series(&mut ctx, series, tmdb_id.map(SeriesId))?;
+        }
+        cli::Command::History { series } => {
+            history::print_history(&mut ctx, series)?;
+        }
+        cli::Command::ExportIcal { file_path, user } => {
+            let user = match user {
+                Some(user) => Some(user::resolve_user(&mut ctx, Some(user))?),
+                None => None,
+            };
+            ical::export_ical(&mut ctx, file_path, user.as_ref())?;
+        }
+        cli::Command::Export { file_path, format } => {
+            export::export_tracked_series(&mut ctx, file_path, *format)?;
+        }
+        cli::Command::Import { file_path, format } => {
+            export::import_tracked_series(&mut ctx, file_path, *format)?;
+        }
+        cli::Command::Restore { backup } => {
+            backup::restore(&mut ctx, backup.as_deref())?;
+        }
+        cli::Command::User { command } => match command {
+            cli::UserCommand::Add { name, email } => {
+                user::add_user(&mut ctx, name, email)?;
+            }
+            cli::UserCommand::List => {
+                user::list_users(&mut ctx)?;
+            }
+            cli::UserCommand::Remove { user } => {
+                user::remove_user(&mut ctx, user)?;
+            }
+        },
+        cli::Command::List {
+            status,
+            user,
+            sort,
+            episodes,
+        } => {
+            list::list_series(&mut ctx, *status, user.as_deref(), *sort, *episodes)?;
+        }
     };
 
     Ok(())
@@ -0,0 +1,92 @@
+use super::Notifier;
+use super::email_html::make_email_html;
+use super::email_text::make_email_text;
+use super::entry::SeriesEntry;
+use crate::{config::AppConfig, db};
+use anyhow::Context;
+use lettre::{
+    Message, SmtpTransport, Transport,
+    message::{Mailbox, MultiPart, SinglePart, header::ContentType},
+    transport::smtp::authentication::Credentials,
+};
+
+/// Builds the e-mail to `user` about `series_entries`, which should be only the series the user is subscribed to.
+pub fn make_email(
+    from_mailbox: &Mailbox,
+    user: &db::User,
+    series_entries: &[&SeriesEntry],
+    date: chrono::NaiveDate,
+) -> anyhow::Result<Message> {
+    // NOTE: we are using CIDs to attach the poster image data inline with the e-mail
+    // this is because we don't have a simple GET url for them without leaking our TMDB API key
+    // however, some e-mail clients don't like CIDs and prefer external images
+    // that is only feasible if we have hosting and a CDN set up though
+    // reading on CIDs:
+    // - https://mailtrap.io/blog/embedding-images-in-html-email-have-the-rules-changed/
+    // - https://stackoverflow.com/a/40420648/128240
+    // - https://users.rust-lang.org/t/add-attachment-to-message-builder-in-lettre-email-sender/68471
+
+    // the plain text part goes first, as clients display the last alternative they understand
+    let email_multipart_contents = MultiPart::alternative()
+        .singlepart(SinglePart::plain(make_email_text(series_entries)))
+        .multipart({
+            let mut multipart = MultiPart::related().singlepart(
+                SinglePart::builder()
+                    .header(ContentType::TEXT_HTML)
+                    .body(make_email_html(series_entries)),
+            );
+
+            for entry in series_entries.iter() {
+                multipart = multipart.singlepart(entry.create_poster_attachment());
+            }
+
+            multipart
+        });
+
+    Message::builder()
+        .from(from_mailbox.clone())
+        .to(Mailbox::new(Some(user.name.clone()), user.email.parse()?))
+        .subject(format!("TVTrack updates {date}"))
+        .multipart(email_multipart_contents)
+        .with_context(|| format!("building email for {user:?}"))
+}
+
+/// Sends notifications as e-mails via SMTP.
+pub struct EmailNotifier {
+    mailer: SmtpTransport,
+    from_mailbox: Mailbox,
+}
+
+impl EmailNotifier {
+    pub fn new(config: &AppConfig) -> anyhow::Result<Self> {
+        let credentials = Credentials::new(config.smtp.user.clone(), config.smtp.password.clone());
+
+        let mailer = SmtpTransport::starttls_relay(&config.smtp.host)
+            .context("Setting up STARTTLS for SMTP")?
+            .port(config.smtp.port)
+            .credentials(credentials)
+            .build();
+
+        let from_mailbox = Mailbox::new(
+            config.emails.from_name.clone(),
+            config.emails.from_address.parse()?,
+        );
+
+        Ok(Self {
+            mailer,
+            from_mailbox,
+        })
+    }
+}
+
+impl Notifier for EmailNotifier {
+    fn notify(&mut self, user: &db::User, series_entries: &[&SeriesEntry]) -> anyhow::Result<()> {
+        let now = chrono::Local::now();
+        let email = make_email(&self.from_mailbox, user, series_entries, now.date_naive())?;
+
+        self.mailer
+            .send(&email)
+            .context("Sending e-mail notifications via SMTP")?;
+        Ok(())
+    }
+}
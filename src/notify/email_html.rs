@@ -2,7 +2,7 @@ use super::entry::SeriesEntry;
 use chrono::Datelike;
 
 // TODO: use some kind of templating?
-pub fn make_email_html(entries: &[SeriesEntry]) -> String {
+pub fn make_email_html(entries: &[&SeriesEntry]) -> String {
     let mut html: String = r###"<!doctype html>
 <html>
     <head>
@@ -120,7 +120,7 @@ table[class=body] .article {
 "###.into();
 
     for i in 0..entries.len() {
-        let entry = &entries[i];
+        let entry = entries[i];
         let is_last = i == entries.len() - 1;
 
         let template = r###"
@@ -131,11 +131,11 @@ table[class=body] .article {
                         <td style="font-family: sans-serif; font-size: 14px; vertical-align: top;" valign="top">
                         <h3 class="series-title" style="color: #06090f; font-family: sans-serif; font-weight: 400; line-height: 1.4; margin: 0; margin-bottom: 7px;">
                             <a href="{{url}}" style="color: #ec0867; text-decoration: underline;">{{title}} ({{release_year}})</a>
-                        </h3>
-                        <ul class="series-changes" style="font-family: sans-serif; font-size: 14px; font-weight: normal; margin: 0; padding: 0; margin-bottom: {{margin_bottom}}px;">
+                        </h3>{{links}}
+                        <ul class="series-changes" style="font-family: sans-serif; font-size: 14px; font-weight: normal; margin: 0; padding: 0; margin-bottom: {{margin_bottom}}px;">{{orphaned}}
                             <li style="list-style-position: inside; margin-left: 5px;">{{in_production}}</li>
                             <li style="list-style-position: inside; margin-left: 5px;">{{status}}</li>
-                            <li style="list-style-position: inside; margin-left: 5px;">Last: {{last_episode}}</li>
+                            <li style="list-style-position: inside; margin-left: 5px;">Last: {{last_episode}}</li>{{aired_episodes}}
                             <li style="list-style-position: inside; margin-left: 5px;">Next: {{next_episode}}</li>
                             <li style="list-style-position: inside; margin-left: 5px;">Episodes: {{episode_count}}</li>
                         </ul>
@@ -159,8 +159,40 @@ table[class=body] .article {
                     .map(|dt| dt.year().to_string())
                     .unwrap_or("unreleased".to_owned()),
             )
+            .replace("{{links}}", &{
+                let links = entry.series.external_ids.links();
+                if links.is_empty() {
+                    String::new()
+                } else {
+                    let links = links
+                        .iter()
+                        .map(|(site, url)| {
+                            format!(
+                                r#"<a href="{url}" style="color: #ec0867; text-decoration: underline;">{site}</a>"#
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" | ");
+                    format!(
+                        r#"
+                        <p class="series-links" style="font-family: sans-serif; font-size: 12px; font-weight: normal; margin: 0; margin-bottom: 7px;">{links}</p>"#
+                    )
+                }
+            })
             .replace("{{url}}", &entry.url)
             .replace("{{poster_url}}", &entry.poster_attachment_uri())
+            .replace(
+                "{{orphaned}}",
+                &if entry.changes.orphaned {
+                    format!(
+                        r#"
+                            <li style="list-style-position: inside; margin-left: 5px;">{}</li>"#,
+                        wrap_changed("Removed from TMDB, no longer being updated")
+                    )
+                } else {
+                    String::new()
+                },
+            )
             .replace(
                 "{{in_production}}",
                 &match entry.changes.in_production_change {
@@ -194,6 +226,25 @@ table[class=body] .article {
                     .map(|ep| ep.identify())
                     .unwrap_or("none".to_owned()),
             )
+            .replace("{{aired_episodes}}", &{
+                // a single aired episode is already reported as the last episode
+                if entry.changes.aired_episodes.len() > 1 {
+                    let aired_list = entry
+                        .changes
+                        .aired_episodes
+                        .iter()
+                        .map(|ep| ep.identify())
+                        .collect::<Vec<_>>()
+                        .join("<br>");
+                    format!(
+                        r#"
+                            <li style="list-style-position: inside; margin-left: 5px;">Aired: {}</li>"#,
+                        wrap_changed(&aired_list)
+                    )
+                } else {
+                    String::new()
+                }
+            })
             .replace("{{next_episode}}", &{
                 let ep_info = entry
                     .series
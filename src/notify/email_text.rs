@@ -0,0 +1,48 @@
+use super::entry::SeriesEntry;
+use crate::tmdb::EpisodeDetails;
+
+/// Plain text version of `email_html::make_email_html`, sent as an alternative for clients that don't do HTML.
+pub fn make_email_text(entries: &[&SeriesEntry]) -> String {
+    let mut text = String::from("TVTrack updates to series you are tracking\n");
+
+    for entry in entries.iter() {
+        let details = &entry.series.details;
+
+        text += "\n";
+        text += &format!("{}\n", details.name);
+        text += &format!("{}\n", entry.url);
+        for (site, url) in entry.series.external_ids.links() {
+            text += &format!("{site}: {url}\n");
+        }
+        text += "Changes:";
+        text += &entry.changes.summary();
+        text += &format!(
+            "Currently: {} | {} | {} episodes\n",
+            details.status,
+            if details.in_production {
+                "in production"
+            } else {
+                "not in production"
+            },
+            details.number_of_episodes
+        );
+        text += &format!(
+            "Last: {}\n",
+            details
+                .last_episode_to_air
+                .as_ref()
+                .map(EpisodeDetails::identify)
+                .unwrap_or("none".to_owned())
+        );
+        text += &format!(
+            "Next: {}\n",
+            details
+                .next_episode_to_air
+                .as_ref()
+                .map(EpisodeDetails::identify)
+                .unwrap_or("unknown".to_owned())
+        );
+    }
+
+    text
+}
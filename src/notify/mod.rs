@@ -1,33 +1,49 @@
+mod email;
 mod email_html;
+mod email_text;
 mod entry;
+mod webhook;
 
-use self::email_html::make_email_html;
 use self::entry::{SeriesEntry, series_changes_to_entries};
-use crate::{AppContext, SeriesDetailsChanges, db};
-use anyhow::Context;
-use lettre::{
-    Message, SmtpTransport, Transport,
-    message::{Mailbox, MultiPart, SinglePart, header::ContentType},
-    transport::smtp::authentication::Credentials,
-};
-use std::collections::HashMap;
-
-pub fn send_email_notifications(
-    ctx: &mut AppContext,
-    changes: &[(db::Series, SeriesDetailsChanges)],
-) -> anyhow::Result<()> {
-    // NOTE: we are using CIDs to attach the poster image data inline with the e-mail
-    // this is because we don't have a simple GET url for them without leaking our TMDB API key
-    // however, some e-mail clients don't like CIDs and prefer external images
-    // that is only feasible if we have hosting and a CDN set up though
-    // reading on CIDs:
-    // - https://mailtrap.io/blog/embedding-images-in-html-email-have-the-rules-changed/
-    // - https://stackoverflow.com/a/40420648/128240
-    // - https://users.rust-lang.org/t/add-attachment-to-message-builder-in-lettre-email-sender/68471
+use crate::{AppContext, SeriesDetailsChanges, config::NotificationChannel, db};
+use anyhow::bail;
+use std::collections::{HashMap, hash_map};
 
-    let entries = series_changes_to_entries(ctx, changes)?;
+/// A channel through which users can be notified about changes to the series they are subscribed to.
+pub trait Notifier {
+    /// Notifies `user` about `series_entries`, which are only the series the user is subscribed to.
+    fn notify(&mut self, user: &db::User, series_entries: &[&SeriesEntry]) -> anyhow::Result<()>;
+
+    /// Called once all users have been notified, for channels that send a single notification per run.
+    fn finish(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+fn make_notifier(
+    ctx: &AppContext,
+    channel: NotificationChannel,
+) -> anyhow::Result<Box<dyn Notifier>> {
+    let notifier: Box<dyn Notifier> = match channel {
+        NotificationChannel::Email => Box::new(email::EmailNotifier::new(&ctx.config)?),
+        NotificationChannel::Webhook => {
+            let Some(webhook_config) = &ctx.config.webhook else {
+                bail!("Webhook notifications are enabled, but there is no webhook config");
+            };
+            Box::new(webhook::WebhookNotifier::new(webhook_config))
+        }
+    };
+    Ok(notifier)
+}
 
-    let mut users_to_entries = HashMap::<i64, (db::User, Vec<&SeriesEntry<'_>>)>::new();
+type UsersToEntries<'a, 'e> = HashMap<i64, (db::User, Vec<&'e SeriesEntry<'a>>)>;
+
+/// Groups the entries by the users subscribed to them, so that each user only gets notified about their own series.
+fn group_entries_by_user<'a, 'e>(
+    ctx: &mut AppContext,
+    entries: &'e [SeriesEntry<'a>],
+) -> anyhow::Result<UsersToEntries<'a, 'e>> {
+    let mut users_to_entries = UsersToEntries::new();
     for entry in entries.iter() {
         let subscribed_users = ctx
             .db
@@ -48,60 +64,236 @@ pub fn send_email_notifications(
                 .or_insert_with(|| (user, vec![entry]));
         }
     }
+    Ok(users_to_entries)
+}
 
-    let credentials = Credentials::new(
-        ctx.config.smtp.user.clone(),
-        ctx.config.smtp.password.clone(),
-    );
-
-    let mailer = SmtpTransport::starttls_relay(&ctx.config.smtp.host)
-        .context("Setting up STARTTLS for SMTP")?
-        .port(ctx.config.smtp.port)
-        .credentials(credentials)
-        .build();
+pub fn send_notifications(
+    ctx: &mut AppContext,
+    changes: &[(db::Series, SeriesDetailsChanges)],
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let entries = series_changes_to_entries(ctx, changes)?;
+    let users_to_entries = group_entries_by_user(ctx, &entries)?;
 
-    let from_mailbox = Mailbox::new(
-        ctx.config.emails.from_name.clone(),
-        ctx.config.emails.from_address.parse()?,
-    );
-    let now = chrono::Local::now();
+    // notifiers are only set up once needed, so that e.g. we don't connect to SMTP if nobody wants e-mails
+    let mut notifiers = HashMap::<NotificationChannel, Box<dyn Notifier>>::new();
+    let mut failure_count = 0;
 
     for (user, series_entries) in users_to_entries.values() {
-        log::info!(
-            "sending email to {user} about {} series: {}",
-            series_entries.len(),
-            series_entries
+        let series_list = series_entries
+            .iter()
+            .map(|e| e.series.details.identify())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        for &channel in ctx.config.notifications.channels_for_user(&user.email) {
+            if dry_run {
+                println!(
+                    "Would notify {user} via {channel} about {} series: {series_list}",
+                    series_entries.len()
+                );
+                continue;
+            }
+
+            log::info!(
+                "notifying {user} via {channel} about {} series: {series_list}",
+                series_entries.len()
+            );
+
+            let notifier = match notifiers.entry(channel) {
+                hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                hash_map::Entry::Vacant(entry) => entry.insert(make_notifier(ctx, channel)?),
+            };
+
+            // one channel failing shouldn't prevent the others from being notified
+            if let Err(err) = notifier.notify(user, series_entries) {
+                log::error!("Failed to notify {user} via {channel}: {err:?}");
+                failure_count += 1;
+            }
+        }
+    }
+
+    for (channel, notifier) in notifiers.iter_mut() {
+        if let Err(err) = notifier.finish() {
+            log::error!("Failed to send notifications via {channel}: {err:?}");
+            failure_count += 1;
+        }
+    }
+
+    if failure_count > 0 {
+        bail!("Failed to send {failure_count} notification(s)");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::email::make_email;
+    use super::email_html::make_email_html;
+    use super::email_text::make_email_text;
+    use super::*;
+    use crate::{testing, tmdb::SeriesStatus};
+    use lettre::message::Mailbox;
+
+    #[test]
+    fn emails_only_contain_the_recipients_own_series() {
+        let mut ctx = testing::make_context();
+        let alice = testing::insert_user(&mut ctx, "Alice", "alice@example.com");
+        let bob = testing::insert_user(&mut ctx, "Bob", "bob@example.com");
+
+        let changes: Vec<_> = [
+            (testing::make_series_details(1, "Only Alice"), vec![&alice]),
+            (testing::make_series_details(2, "Both"), vec![&alice, &bob]),
+            (testing::make_series_details(3, "Only Bob"), vec![&bob]),
+        ]
+        .into_iter()
+        .map(|(details, users)| {
+            let series = testing::insert_series(&mut ctx, details, &users);
+            let mut changes = SeriesDetailsChanges::new(series.tmdb_id);
+            changes.status_change = Some((SeriesStatus::InProduction, series.status));
+            (series, changes)
+        })
+        .collect();
+
+        let entries = series_changes_to_entries(&mut ctx, &changes).unwrap();
+        let users_to_entries = group_entries_by_user(&mut ctx, &entries).unwrap();
+        assert_eq!(users_to_entries.len(), 2);
+
+        let from_mailbox = Mailbox::new(None, "tvtrack@example.com".parse().unwrap());
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 1, 9).unwrap();
+
+        for (user, expected_titles, unexpected_titles) in [
+            (&alice, ["Only Alice", "Both"], ["Only Bob"]),
+            (&bob, ["Both", "Only Bob"], ["Only Alice"]),
+        ] {
+            let (_, user_entries) = &users_to_entries[&user.id];
+            let titles: Vec<_> = user_entries
                 .iter()
-                .map(|e| e.series.details.identify())
-                .collect::<Vec<_>>()
-                .join(", ")
-        );
+                .map(|e| e.series.title.as_str())
+                .collect();
+            assert_eq!(titles, expected_titles);
+
+            let html = make_email_html(user_entries);
+            let email = make_email(&from_mailbox, user, user_entries, date).unwrap();
+            let formatted = String::from_utf8(email.formatted()).unwrap();
 
-        let email_multipart_contents = MultiPart::mixed().multipart({
-            let mut multipart = MultiPart::related().singlepart(
-                SinglePart::builder()
-                    .header(ContentType::TEXT_HTML)
-                    .body(make_email_html(&entries)),
+            assert_eq!(email.envelope().to(), [user.email.parse().unwrap()]);
+            assert_eq!(
+                formatted.matches("Content-ID: <").count(),
+                expected_titles.len()
             );
 
-            for entry in series_entries.iter() {
-                multipart = multipart.singlepart(entry.create_poster_attachment());
+            for entry in user_entries.iter() {
+                assert!(html.contains(&format!(">{} (2024)</a>", entry.series.title)));
+                assert!(html.contains(&entry.poster_attachment_uri()));
+                assert!(
+                    formatted.contains(&format!("Content-ID: <{}>", entry.poster_attachment_id()))
+                );
             }
 
-            multipart
-        });
+            for other_entry in entries
+                .iter()
+                .filter(|e| unexpected_titles.contains(&e.series.title.as_str()))
+            {
+                assert!(!html.contains(&other_entry.series.title));
+                assert!(!html.contains(&other_entry.poster_attachment_uri()));
+                assert!(!formatted.contains(&other_entry.poster_attachment_id()));
+            }
+        }
+    }
+
+    #[test]
+    fn email_is_plain_text_alternative_to_html_with_inline_posters() {
+        let mut ctx = testing::make_context();
+        let alice = testing::insert_user(&mut ctx, "Alice", "alice@example.com");
+        let series = testing::insert_series(
+            &mut ctx,
+            testing::make_series_details(1, "Some Series"),
+            &[&alice],
+        );
+        let mut changes = SeriesDetailsChanges::new(series.tmdb_id);
+        changes.episode_count_change = Some((8, 10));
+        let changes = [(series, changes)];
+
+        let entries = series_changes_to_entries(&mut ctx, &changes).unwrap();
+        let user_entries: Vec<_> = entries.iter().collect();
 
-        let email = Message::builder()
-            .from(from_mailbox.clone())
-            .to(Mailbox::new(Some(user.name.clone()), user.email.parse()?))
-            .subject(format!("TVTrack updates {}", now.date_naive()))
-            .multipart(email_multipart_contents)
-            .with_context(|| format!("building email for {user:?}"))?;
+        let text = make_email_text(&user_entries);
+        assert!(text.contains("Some Series\nhttps://www.themoviedb.org/tv/1\n"));
+        assert!(text.contains(" - Episode count: 8 => 10\n"));
+        assert!(text.contains("Next: S01E02 Episode 2 on 2024-01-08\n"));
 
-        mailer
-            .send(&email)
-            .context("Sending e-mail notifications via SMTP")?;
+        let from_mailbox = Mailbox::new(None, "tvtrack@example.com".parse().unwrap());
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 1, 9).unwrap();
+        let email = make_email(&from_mailbox, &alice, &user_entries, date).unwrap();
+        let formatted = String::from_utf8(email.formatted()).unwrap();
+
+        // the top-level part is multipart/alternative: text/plain first, then the HTML with its inline posters
+        let content_types: Vec<_> = formatted
+            .lines()
+            .filter_map(|line| line.strip_prefix("Content-Type: "))
+            .map(|ct| ct.split(';').next().unwrap())
+            .collect();
+        assert_eq!(
+            content_types,
+            [
+                "multipart/alternative",
+                "text/plain",
+                "multipart/related",
+                "text/html",
+                "image/jpeg"
+            ]
+        );
+        assert!(formatted.contains(&format!(
+            "Content-ID: <{}>",
+            entries[0].poster_attachment_id()
+        )));
     }
 
-    Ok(())
+    #[test]
+    fn update_notifies_only_subscribers_via_configured_channels() {
+        let (url, received) = testing::start_http_server(vec![200]);
+        let mut ctx = testing::make_context();
+        ctx.config.notifications.default_channels = vec![NotificationChannel::Webhook];
+        ctx.config.webhook = Some(crate::config::WebhookConfig {
+            urls: vec![url + "/hook"],
+            secret: None,
+            max_attempts: 1,
+            retry_delay_ms: 1,
+            timeout_secs: 5,
+        });
+
+        let alice = testing::insert_user(&mut ctx, "Alice", "alice@example.com");
+        testing::insert_user(&mut ctx, "Bob", "bob@example.com");
+        let old_details =
+            testing::make_outdated_series_details(&mut ctx, crate::tmdb::SeriesId(108545));
+        testing::insert_series(&mut ctx, old_details, &[&alice]);
+
+        let changes = crate::update::update_all_series(&mut ctx, true, false).unwrap();
+        assert_eq!(changes.len(), 1);
+        send_notifications(&mut ctx, &changes, false).unwrap();
+
+        let request = received.recv().unwrap();
+        assert_eq!(request.url, "/hook");
+        let payload: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(payload["series"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            payload["series"][0]["subscribers"],
+            serde_json::json!([{ "id": alice.id, "name": "Alice", "email": "alice@example.com" }])
+        );
+        assert_eq!(payload["series"][0]["title"], "3 Body Problem");
+        assert_eq!(
+            payload["series"][0]["imdb_url"],
+            "https://www.imdb.com/title/tt13016388/"
+        );
+        assert_eq!(
+            payload["series"][0]["changes"]["aired_episodes"]
+                .as_array()
+                .unwrap()
+                .len(),
+            3
+        );
+        assert!(received.try_recv().is_err());
+    }
 }
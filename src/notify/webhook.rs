@@ -0,0 +1,379 @@
+//! Notifications as JSON POSTed to arbitrary URLs, for plugging into other automation.
+//!
+//! Every run sends a single request to each configured URL, once all users have been notified, listing every
+//! changed series once along with the users subscribed to it, with a JSON body like:
+//!
+//! ```json
+//! {
+//!   "timestamp": "2024-05-10T06:00:00Z",
+//!   "series": [
+//!     {
+//!       "id": 108545,
+//!       "title": "3 Body Problem",
+//!       "url": "https://www.themoviedb.org/tv/108545",
+//!       "external_ids": { "imdb_id": "tt13016388", "tvdb_id": 411384, "wikidata_id": null },
+//!       "imdb_url": "https://www.imdb.com/title/tt13016388/",
+//!       "tvdb_url": "https://thetvdb.com/dereferrer/series/411384",
+//!       "subscribers": [{ "id": 1, "name": "Alice", "email": "alice@example.com" }],
+//!       "changes": {
+//!         "in_production": { "old": true, "new": false },
+//!         "status": { "old": "Returning Series", "new": "Ended" },
+//!         "last_episode": { "old": <episode>, "new": <episode> },
+//!         "next_episode": { "old": <episode>, "new": null },
+//!         "episode_count": { "old": 8, "new": 10 },
+//!         "aired_episodes": [<episode>, ...],
+//!         "added_episodes": [<episode>, ...],
+//!         "removed_episodes": [<episode>, ...],
+//!         "orphaned": false
+//!       }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Each field of `changes` is `null` if it didn't change. `orphaned` is `true` (and everything else is unchanged)
+//! if the series no longer exists on TMDB. Only the users notified via webhook are listed as `subscribers`. Episodes are formatted as in the TMDB API, e.g.
+//! `{ "id": 5051968, "season_number": 1, "episode_number": 8, "name": "Wallfacer", "episode_type": "finale", "air_date": "2024-03-21" }`.
+//!
+//! If a secret is configured, the `X-TVTrack-Signature` header is set to `sha256=` followed by
+//! the hex-encoded HMAC-SHA256 of the request body.
+//!
+//! Failed deliveries due to network errors, HTTP 429 or 5xx responses are retried with exponential backoff.
+
+use super::Notifier;
+use super::entry::SeriesEntry;
+use crate::{
+    config::WebhookConfig,
+    db,
+    tmdb::{EpisodeDetails, ExternalIds, SeriesStatus},
+};
+use anyhow::{Context, bail};
+use hmac::Mac;
+use serde::Serialize;
+use std::time::Duration;
+
+pub const SIGNATURE_HEADER: &str = "X-TVTrack-Signature";
+
+#[derive(Debug, Serialize)]
+pub struct Change<T> {
+    pub old: T,
+    pub new: T,
+}
+
+impl<T: Clone> Change<T> {
+    fn from_pair(pair: &Option<(T, T)>) -> Option<Self> {
+        pair.as_ref().map(|(old, new)| Change {
+            old: old.clone(),
+            new: new.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangesPayload {
+    pub in_production: Option<Change<bool>>,
+    pub status: Option<Change<SeriesStatus>>,
+    pub last_episode: Option<Change<Option<EpisodeDetails>>>,
+    pub next_episode: Option<Change<Option<EpisodeDetails>>>,
+    pub episode_count: Option<Change<i32>>,
+    pub aired_episodes: Vec<EpisodeDetails>,
+    pub added_episodes: Vec<EpisodeDetails>,
+    pub removed_episodes: Vec<EpisodeDetails>,
+    pub orphaned: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserPayload {
+    pub id: i64,
+    pub name: String,
+    pub email: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SeriesPayload {
+    pub id: i32,
+    pub title: String,
+    pub url: String,
+    pub external_ids: ExternalIds,
+    pub imdb_url: Option<String>,
+    pub tvdb_url: Option<String>,
+    pub subscribers: Vec<UserPayload>,
+    pub changes: ChangesPayload,
+}
+
+impl SeriesPayload {
+    /// The series without any subscribers yet.
+    fn new(entry: &SeriesEntry) -> Self {
+        let changes = entry.changes;
+        Self {
+            id: entry.series.tmdb_id.0,
+            title: entry.series.details.name.clone(),
+            url: entry.url.clone(),
+            external_ids: entry.series.external_ids.clone(),
+            imdb_url: entry.series.external_ids.imdb_url(),
+            tvdb_url: entry.series.external_ids.tvdb_url(),
+            subscribers: Vec::new(),
+            changes: ChangesPayload {
+                in_production: Change::from_pair(&changes.in_production_change),
+                status: Change::from_pair(&changes.status_change),
+                last_episode: Change::from_pair(&changes.last_episode_change),
+                next_episode: Change::from_pair(&changes.next_episode_change),
+                episode_count: Change::from_pair(&changes.episode_count_change),
+                aired_episodes: changes.aired_episodes.clone(),
+                added_episodes: changes.added_episodes.clone(),
+                removed_episodes: changes.removed_episodes.clone(),
+                orphaned: changes.orphaned,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookPayload {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub series: Vec<SeriesPayload>,
+}
+
+impl WebhookPayload {
+    pub fn new() -> Self {
+        Self {
+            timestamp: chrono::Utc::now(),
+            series: Vec::new(),
+        }
+    }
+
+    /// Adds `user` as a subscriber of `series_entries`, adding the series that aren't in the payload yet.
+    pub fn add_subscriber(&mut self, user: &db::User, series_entries: &[&SeriesEntry]) {
+        for entry in series_entries.iter() {
+            let idx = match self
+                .series
+                .iter()
+                .position(|s| s.id == entry.series.tmdb_id.0)
+            {
+                Some(idx) => idx,
+                None => {
+                    self.series.push(SeriesPayload::new(entry));
+                    self.series.len() - 1
+                }
+            };
+
+            let subscribers = &mut self.series[idx].subscribers;
+            subscribers.push(UserPayload {
+                id: user.id,
+                name: user.name.clone(),
+                email: user.email.clone(),
+            });
+            subscribers.sort_by_key(|subscriber| subscriber.id);
+        }
+    }
+}
+
+/// Computes the value of the `SIGNATURE_HEADER` for the given request body.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+
+    let mut signature = String::from("sha256=");
+    for byte in mac.finalize().into_bytes() {
+        signature += &format!("{byte:02x}");
+    }
+    signature
+}
+
+pub struct WebhookNotifier {
+    agent: ureq::Agent,
+    config: WebhookConfig,
+    payload: WebhookPayload,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: &WebhookConfig) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(config.timeout_secs)))
+            .build()
+            .into();
+
+        Self {
+            agent,
+            config: config.clone(),
+            payload: WebhookPayload::new(),
+        }
+    }
+    fn post(&self, url: &str, body: &str) -> anyhow::Result<()> {
+        let mut retry_delay = Duration::from_millis(self.config.retry_delay_ms);
+        let mut attempt = 1;
+
+        loop {
+            let mut request = self.agent.post(url).content_type("application/json");
+            if let Some(secret) = &self.config.secret {
+                request = request.header(SIGNATURE_HEADER, &sign(secret, body.as_bytes()));
+            }
+
+            let err = match request.send(body) {
+                Ok(_) => return Ok(()),
+                Err(err) => err,
+            };
+
+            let is_retriable = match err {
+                ureq::Error::StatusCode(status) => status == 429 || status >= 500,
+                _ => true,
+            };
+            if !is_retriable || attempt >= self.config.max_attempts {
+                return Err(err).with_context(|| {
+                    format!("POSTing webhook payload to {url} (attempt {attempt})")
+                });
+            }
+
+            log::warn!(
+                "Webhook {url} attempt {attempt} failed, retrying in {retry_delay:?}: {err}"
+            );
+            std::thread::sleep(retry_delay);
+            retry_delay *= 2;
+            attempt += 1;
+        }
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    /// Only collects the series, they are sent by `finish` once all users have been notified.
+    fn notify(&mut self, user: &db::User, series_entries: &[&SeriesEntry]) -> anyhow::Result<()> {
+        self.payload.add_subscriber(user, series_entries);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        let payload = std::mem::replace(&mut self.payload, WebhookPayload::new());
+        if payload.series.is_empty() {
+            return Ok(());
+        }
+        let body = serde_json::to_string(&payload).context("Serializing webhook payload")?;
+
+        let mut failure_count = 0;
+        for url in self.config.urls.iter() {
+            if let Err(err) = self.post(url, &body) {
+                log::error!("Failed to deliver webhook to {url}: {err:?}");
+                failure_count += 1;
+            }
+        }
+
+        if failure_count > 0 {
+            bail!(
+                "Failed to deliver webhook to {failure_count} of {} URL(s)",
+                self.config.urls.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        notify::entry::series_changes_to_entries, testing, tmdb::SeriesId,
+        update::SeriesDetailsChanges,
+    };
+
+    fn make_config(url: String, max_attempts: u32) -> WebhookConfig {
+        WebhookConfig {
+            urls: vec![url],
+            secret: Some("s3cr3t".to_owned()),
+            max_attempts,
+            retry_delay_ms: 1,
+            timeout_secs: 5,
+        }
+    }
+
+    #[test]
+    fn posts_one_signed_payload_per_run_and_retries_server_errors() {
+        let mut ctx = testing::make_context();
+        let alice = testing::insert_user(&mut ctx, "Alice", "alice@example.com");
+        let bob = testing::insert_user(&mut ctx, "Bob", "bob@example.com");
+        let changes: Vec<_> = [
+            (
+                testing::make_series_details(1, "Some Series"),
+                vec![&alice, &bob],
+            ),
+            (testing::make_series_details(2, "Other Series"), vec![&bob]),
+        ]
+        .into_iter()
+        .map(|(details, users)| {
+            let series = testing::insert_series(&mut ctx, details, &users);
+            let mut changes = SeriesDetailsChanges::new(series.tmdb_id);
+            changes.status_change = Some((SeriesStatus::ReturningSeries, SeriesStatus::Ended));
+            changes.episode_count_change = Some((8, 10));
+            (series, changes)
+        })
+        .collect();
+        let entries = series_changes_to_entries(&mut ctx, &changes).unwrap();
+
+        let (url, received) = testing::start_http_server(vec![503, 200]);
+        let mut notifier = WebhookNotifier::new(&make_config(url + "/hook", 3));
+        notifier.notify(&bob, &[&entries[1], &entries[0]]).unwrap();
+        notifier.notify(&alice, &[&entries[0]]).unwrap();
+        assert!(received.try_recv().is_err());
+        notifier.finish().unwrap();
+
+        let first = received.recv().unwrap();
+        let second = received.recv().unwrap();
+        assert_eq!(first.body, second.body);
+        assert_eq!(
+            second.header(SIGNATURE_HEADER),
+            Some(sign("s3cr3t", second.body.as_bytes()))
+        );
+
+        let payload: serde_json::Value = serde_json::from_str(&second.body).unwrap();
+        let series = payload["series"].as_array().unwrap();
+        assert_eq!(series.len(), 2);
+        assert_eq!(series[0]["id"], 2);
+        assert_eq!(series[0]["subscribers"][0]["email"], "bob@example.com");
+        let series = &series[1];
+        assert_eq!(series["id"], 1);
+        assert_eq!(series["title"], "Some Series");
+        assert_eq!(series["url"], "https://www.themoviedb.org/tv/1");
+        let subscribers: Vec<_> = series["subscribers"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|user| user["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(subscribers, ["Alice", "Bob"]);
+        assert_eq!(
+            series["changes"]["status"],
+            serde_json::json!({ "old": "Returning Series", "new": "Ended" })
+        );
+        assert_eq!(
+            series["changes"]["episode_count"],
+            serde_json::json!({ "old": 8, "new": 10 })
+        );
+        assert!(series["changes"]["in_production"].is_null());
+        assert_eq!(series["changes"]["aired_episodes"], serde_json::json!([]));
+
+        // everything was sent already, so there is nothing left for another request
+        notifier.finish().unwrap();
+        assert!(received.try_recv().is_err());
+    }
+
+    #[test]
+    fn gives_up_on_client_errors_without_retrying() {
+        let mut ctx = testing::make_context();
+        let alice = testing::insert_user(&mut ctx, "Alice", "alice@example.com");
+        let series = testing::insert_series(
+            &mut ctx,
+            testing::make_series_details(1, "Some Series"),
+            &[&alice],
+        );
+        let changes = [(series, SeriesDetailsChanges::new(SeriesId(1)))];
+        let entries = series_changes_to_entries(&mut ctx, &changes).unwrap();
+
+        // a retry would get the 200 and succeed
+        let (url, received) = testing::start_http_server(vec![400, 200]);
+        let mut notifier = WebhookNotifier::new(&make_config(url + "/hook", 3));
+        notifier.notify(&alice, &[&entries[0]]).unwrap();
+        assert!(notifier.finish().is_err());
+
+        received.recv().unwrap();
+        assert!(received.try_recv().is_err());
+    }
+}
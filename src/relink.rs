@@ -0,0 +1,179 @@
+use anyhow::{Context, bail};
+use chrono::Datelike;
+
+use crate::{add, cli::PickStrategy, remove};
+
+use super::{AppContext, SeriesId};
+
+/// Replaces the series with another TMDB series (fetching it if we don't track it yet), moving over all subscriptions.
+/// Without `new_id`, TMDB is searched for the title of the series.
+pub fn relink_series(
+    ctx: &mut AppContext,
+    id_or_title: &str,
+    new_id: Option<SeriesId>,
+) -> anyhow::Result<()> {
+    let Some(old_series) = ctx.db.find_series(id_or_title)? else {
+        bail!("No tracked series with ID or title {id_or_title:?}")
+    };
+
+    if old_series.orphaned_timestamp.is_none() {
+        log::warn!(
+            "Series {} still exists on TMDB, relinking it anyway",
+            old_series.details.identify()
+        );
+    }
+
+    let new_id = match new_id {
+        Some(new_id) => new_id,
+        None => {
+            let first_air_year = old_series.first_air_date.0.map(|dt| dt.year());
+            let best_match = match add::find_best_match(
+                ctx,
+                &old_series.title,
+                first_air_year,
+                PickStrategy::Newest,
+            )? {
+                // the new series may well have a different release year, e.g. if it was merged into an earlier one
+                None if first_air_year.is_some() => {
+                    add::find_best_match(ctx, &old_series.title, None, PickStrategy::Newest)?
+                }
+                best_match => best_match,
+            };
+            let Some(best_match) = best_match else {
+                bail!(
+                    "Could not find a series on TMDB to relink {} to, specify --tmdb-id",
+                    old_series.details.identify()
+                )
+            };
+            best_match.id
+        }
+    };
+
+    if new_id == old_series.tmdb_id {
+        bail!(
+            "Series {} is already linked to TMDB ID {new_id}",
+            old_series.details.identify()
+        );
+    }
+
+    // fetched before the transaction, so that a failure later on doesn't leave the new series behind
+    let new_series_to_store = match ctx.db.get_series_by_id(new_id)? {
+        Some(_) => None,
+        None => Some(add::fetch_new_series(ctx, new_id)?),
+    };
+
+    ctx.db.in_transaction(|db| {
+        let new_series = match new_series_to_store {
+            Some(new_series) => add::store_new_series(db, new_series)?,
+            None => db
+                .get_series_by_id(new_id)?
+                .with_context(|| format!("Series {new_id} disappeared from the database"))?,
+        };
+
+        log::info!(
+            "Relinking series {} to {}",
+            old_series.details.identify(),
+            new_series.details.identify()
+        );
+
+        // users already tracking the new series keep their own start timestamp
+        let moved_count = db
+            .conn
+            .execute(
+                "INSERT OR IGNORE INTO tracked_series (user_id, series_tmdb_id, start_timestamp) SELECT user_id, :new_id, start_timestamp FROM tracked_series WHERE series_tmdb_id = :old_id",
+                rusqlite::named_params! {
+                    ":old_id": old_series.tmdb_id,
+                    ":new_id": new_series.tmdb_id,
+                },
+            )
+            .with_context(|| format!("Moving subscriptions of series {}", old_series.details))?;
+        db.conn
+            .execute(
+                "DELETE FROM tracked_series WHERE series_tmdb_id = ?",
+                (old_series.tmdb_id,),
+            )
+            .with_context(|| format!("Deleting subscriptions of series {}", old_series.details))?;
+
+        // the history of the old series is still relevant to its subscribers
+        db.conn
+            .execute(
+                "UPDATE series_history SET series_tmdb_id = :new_id WHERE series_tmdb_id = :old_id",
+                rusqlite::named_params! {
+                    ":old_id": old_series.tmdb_id,
+                    ":new_id": new_series.tmdb_id,
+                },
+            )
+            .with_context(|| format!("Moving history of series {}", old_series.details))?;
+
+        remove::purge_series(&db.conn, &old_series)?;
+
+        log::info!("-- Moved {moved_count} subscription(s)");
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn relinking_moves_subscriptions_to_the_new_series() {
+        let mut ctx = testing::make_context();
+        let alice = testing::insert_user(&mut ctx, "Alice", "alice@example.com");
+        let bob = testing::insert_user(&mut ctx, "Bob", "bob@example.com");
+
+        let mut series = testing::insert_series(
+            &mut ctx,
+            testing::make_series_details(1, "3 Body Problem"),
+            &[&alice, &bob],
+        );
+        let changes = crate::update::update_one_series(&mut ctx, &mut series, true, false)
+            .unwrap()
+            .unwrap();
+        assert!(changes.orphaned);
+
+        relink_series(&mut ctx, "1", Some(SeriesId(108545))).unwrap();
+
+        assert!(ctx.db.get_series_by_id(SeriesId(1)).unwrap().is_none());
+        assert!(ctx.db.get_poster_by_id(series.poster_id).unwrap().is_none());
+        for user in [&alice, &bob] {
+            let tracked = ctx.db.get_all_series_tracked_by_user(user.id).unwrap();
+            let tracked: Vec<_> = tracked.iter().map(|s| s.tmdb_id).collect();
+            assert_eq!(tracked, [SeriesId(108545)]);
+        }
+
+        let start_timestamps: Vec<chrono::DateTime<chrono::Utc>> = ctx
+            .db
+            .conn
+            .prepare("SELECT start_timestamp FROM tracked_series")
+            .unwrap()
+            .query_map((), |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(start_timestamps, [series.update_timestamp; 2]);
+    }
+
+    #[test]
+    fn failed_relink_leaves_everything_as_it_was() {
+        let mut ctx = testing::make_context();
+        let alice = testing::insert_user(&mut ctx, "Alice", "alice@example.com");
+        testing::insert_series(
+            &mut ctx,
+            testing::make_series_details(1, "3 Body Problem"),
+            &[&alice],
+        );
+        // the last step of relinking
+        testing::inject_failure(&mut ctx, "DELETE", "posters");
+
+        let err = relink_series(&mut ctx, "1", Some(SeriesId(108545))).unwrap_err();
+        assert!(format!("{err:#}").contains("injected failure"));
+
+        assert!(ctx.db.get_series_by_id(SeriesId(108545)).unwrap().is_none());
+        assert_eq!(testing::count_rows(&mut ctx, "seasons"), 0);
+        let tracked = ctx.db.get_all_series_tracked_by_user(alice.id).unwrap();
+        let tracked: Vec<_> = tracked.iter().map(|s| s.tmdb_id).collect();
+        assert_eq!(tracked, [SeriesId(1)]);
+    }
+}
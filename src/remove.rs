@@ -0,0 +1,152 @@
+use anyhow::{Context, bail};
+
+use crate::db;
+
+use super::AppContext;
+
+/// Stops tracking the series for the given user. If `purge` is set and no other users track the series,
+/// the series and its poster are deleted as well.
+/// Returns whether the user was tracking the series.
+pub fn remove_series(
+    ctx: &mut AppContext,
+    series: &db::Series,
+    user: &db::User,
+    purge: bool,
+) -> anyhow::Result<bool> {
+    log::info!("Removing series {} for user {user}", series.details);
+
+    let tx = ctx.db.conn.transaction()?;
+
+    let removed_count = tx
+        .execute(
+            "DELETE FROM tracked_series WHERE user_id = :user_id AND series_tmdb_id = :series_id",
+            rusqlite::named_params! {
+                ":user_id": user.id,
+                ":series_id": series.tmdb_id,
+            },
+        )
+        .with_context(|| format!("Deleting tracked series {} for user {user}", series.details))?;
+
+    if removed_count == 0 {
+        log::warn!("-- Ignoring: series is not tracked by user {user}");
+    }
+
+    let remaining_subscribers: i64 = tx
+        .query_row(
+            "SELECT COUNT(*) FROM tracked_series WHERE series_tmdb_id = ?",
+            (series.tmdb_id,),
+            |row| row.get(0),
+        )
+        .with_context(|| format!("Counting users tracking series {}", series.details))?;
+
+    if remaining_subscribers > 0 {
+        log::info!("-- Series is still tracked by {remaining_subscribers} other user(s)");
+    } else if purge {
+        log::info!("-- No users track the series any more, deleting it");
+        purge_series(&tx, series)?;
+    } else {
+        log::info!("-- No users track the series any more; use `remove --purge` to also delete it");
+    }
+
+    tx.commit()
+        .with_context(|| format!("Committing removal of series {}", series.details))?;
+
+    Ok(removed_count > 0)
+}
+
+/// Deletes the series along with its seasons, episodes, history and poster. It must not be tracked by anyone any more.
+pub fn purge_series(conn: &rusqlite::Connection, series: &db::Series) -> anyhow::Result<()> {
+    conn.execute(
+        "DELETE FROM series_history WHERE series_tmdb_id = ?",
+        (series.tmdb_id,),
+    )
+    .with_context(|| format!("Deleting history of series {}", series.details))?;
+    conn.execute(
+        "DELETE FROM episodes WHERE series_tmdb_id = ?",
+        (series.tmdb_id,),
+    )
+    .with_context(|| format!("Deleting episodes of series {}", series.details))?;
+    conn.execute(
+        "DELETE FROM seasons WHERE series_tmdb_id = ?",
+        (series.tmdb_id,),
+    )
+    .with_context(|| format!("Deleting seasons of series {}", series.details))?;
+    conn.execute("DELETE FROM series WHERE tmdb_id = ?", (series.tmdb_id,))
+        .with_context(|| format!("Deleting series {}", series.details))?;
+
+    // posters are not shared between series, but be defensive about it anyway
+    conn.execute(
+        "DELETE FROM posters WHERE id = :poster_id AND NOT EXISTS (SELECT 1 FROM series WHERE poster_id = :poster_id)",
+        rusqlite::named_params! { ":poster_id": series.poster_id },
+    )
+    .with_context(|| format!("Deleting poster of series {}", series.details))?;
+
+    Ok(())
+}
+
+pub fn remove_series_by_id_or_title(
+    ctx: &mut AppContext,
+    id_or_title: &str,
+    user: &db::User,
+    purge: bool,
+) -> anyhow::Result<bool> {
+    let Some(series) = ctx.db.find_series(id_or_title)? else {
+        bail!("No tracked series with ID or title {id_or_title:?}")
+    };
+
+    remove_series(ctx, &series, user, purge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing, tmdb};
+
+    #[test]
+    fn failed_purge_keeps_the_subscription() {
+        let mut ctx = testing::make_context();
+        let alice = testing::insert_user(&mut ctx, "Alice", "alice@example.com");
+        let series = testing::insert_series(
+            &mut ctx,
+            testing::make_series_details(1, "Some Series"),
+            &[&alice],
+        );
+        // the last step of purging a series
+        testing::inject_failure(&mut ctx, "DELETE", "posters");
+
+        assert!(remove_series(&mut ctx, &series, &alice, true).is_err());
+        assert_eq!(
+            ctx.db
+                .get_all_series_tracked_by_user(alice.id)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(testing::count_rows(&mut ctx, "posters"), 1);
+
+        ctx.db
+            .conn
+            .execute_batch("DROP TRIGGER fail_DELETE_posters")
+            .unwrap();
+        assert!(remove_series(&mut ctx, &series, &alice, true).unwrap());
+        assert_eq!(testing::count_rows(&mut ctx, "series"), 0);
+        assert_eq!(testing::count_rows(&mut ctx, "posters"), 0);
+    }
+
+    #[test]
+    fn find_series_falls_back_to_numeric_title() {
+        let mut ctx = testing::make_context();
+        let alice = testing::insert_user(&mut ctx, "Alice", "alice@example.com");
+        testing::insert_series(
+            &mut ctx,
+            testing::make_series_details(71728, "1883"),
+            &[&alice],
+        );
+
+        let by_title = ctx.db.find_series("1883").unwrap().unwrap();
+        assert_eq!(by_title.details.id, tmdb::SeriesId(71728));
+        let by_id = ctx.db.find_series("71728").unwrap().unwrap();
+        assert_eq!(by_id.details.id, tmdb::SeriesId(71728));
+        assert!(ctx.db.find_series("24").unwrap().is_none());
+    }
+}
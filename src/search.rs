@@ -0,0 +1,122 @@
+use serde::Serialize;
+
+use crate::{add, tmdb};
+
+use super::AppContext;
+
+#[derive(Debug, Serialize)]
+pub struct SearchResultEntry {
+    #[serde(flatten)]
+    pub series: tmdb::SeriesFound,
+
+    /// Whether we are already tracking the series.
+    pub tracked: bool,
+}
+
+/// Searches TMDB for series with the given title, fetching at most `max_pages` pages of results.
+/// Returns the results along with the total number of results that TMDB has.
+pub fn collect_search_results(
+    ctx: &mut AppContext,
+    title: &str,
+    first_air_year: Option<i32>,
+    max_pages: i32,
+) -> anyhow::Result<(Vec<SearchResultEntry>, i32)> {
+    let mut entries = Vec::new();
+    let mut total_results = 0;
+
+    for page in 1..=max_pages {
+        let search_results = ctx.tmdb.search_series(title, first_air_year, page)?;
+        total_results = search_results.total_results;
+
+        for series in search_results.results {
+            let tracked = ctx.db.get_series_by_id(series.id)?.is_some();
+            entries.push(SearchResultEntry { series, tracked });
+        }
+
+        if page >= search_results.total_pages {
+            break;
+        }
+    }
+
+    Ok((entries, total_results))
+}
+
+pub fn search_series(
+    ctx: &mut AppContext,
+    title: &str,
+    first_air_year: Option<i32>,
+    max_pages: i32,
+    json: bool,
+) -> anyhow::Result<()> {
+    let (entries, total_results) = collect_search_results(ctx, title, first_air_year, max_pages)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    for entry in entries.iter() {
+        println!(
+            "{} {}",
+            if entry.tracked { "*" } else { " " },
+            add::format_candidate(&entry.series)
+        );
+    }
+
+    println!(
+        "{} of {total_results} results (* = already tracked)",
+        entries.len()
+    );
+    if (entries.len() as i32) < total_results {
+        println!("Use --max-pages to see more");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing, tmdb::SeriesId};
+
+    #[test]
+    fn collects_results_across_pages_and_marks_tracked_ones() {
+        let mut ctx = testing::make_context();
+        testing::insert_series(&mut ctx, testing::make_series_details(2, "Page 2"), &[]);
+
+        let make_page = |page, ids: &[i32]| tmdb::SearchResults {
+            page,
+            results: ids
+                .iter()
+                .map(|&id| tmdb::SeriesFound {
+                    id: SeriesId(id),
+                    name: format!("Page {page}"),
+                    overview: String::new(),
+                    first_air_date: tmdb::OptionalDate(None),
+                    popularity: 1.0,
+                    origin_country: Vec::new(),
+                })
+                .collect(),
+            total_pages: 3,
+            total_results: 5,
+        };
+        ctx.tmdb = Box::new(
+            tmdb::FixtureClient::new()
+                .with_search_results("Page", make_page(1, &[1]))
+                .with_search_results("Page", make_page(2, &[2, 3]))
+                .with_search_results("Page", make_page(3, &[4, 5])),
+        );
+
+        let (entries, total_results) = collect_search_results(&mut ctx, "Page", None, 2).unwrap();
+        assert_eq!(total_results, 5);
+        let ids: Vec<_> = entries.iter().map(|e| (e.series.id.0, e.tracked)).collect();
+        assert_eq!(ids, [(1, false), (2, true), (3, false)]);
+
+        let (entries, _) = collect_search_results(&mut ctx, "Page", None, 10).unwrap();
+        assert_eq!(entries.len(), 5);
+
+        let json = serde_json::to_value(&entries[1]).unwrap();
+        assert_eq!(json["id"], 2);
+        assert_eq!(json["tracked"], true);
+    }
+}
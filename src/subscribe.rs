@@ -0,0 +1,35 @@
+use anyhow::bail;
+
+use crate::{db, remove};
+
+use super::AppContext;
+
+/// Subscribes the user to a series that is already in the database, without fetching anything from TMDB.
+/// Returns `false` if the user was already subscribed.
+pub fn subscribe(ctx: &mut AppContext, id_or_title: &str, user: &db::User) -> anyhow::Result<bool> {
+    let Some(series) = ctx.db.find_series(id_or_title)? else {
+        bail!("No tracked series with ID or title {id_or_title:?}, add it first")
+    };
+
+    let subscribed = ctx
+        .db
+        .insert_tracked_series(user.id, series.tmdb_id, chrono::Utc::now())?;
+    if subscribed {
+        log::info!("Subscribed user {user} to series {}", series.details);
+    } else {
+        log::warn!(
+            "Ignoring: user {user} is already subscribed to series {}",
+            series.details
+        );
+    }
+    Ok(subscribed)
+}
+
+/// Unsubscribes the user from the series, but keeps the series in the database even if nobody tracks it any more.
+pub fn unsubscribe(
+    ctx: &mut AppContext,
+    id_or_title: &str,
+    user: &db::User,
+) -> anyhow::Result<bool> {
+    remove::remove_series_by_id_or_title(ctx, id_or_title, user, false)
+}
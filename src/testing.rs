@@ -0,0 +1,207 @@
+//! Helpers for setting up an `AppContext` backed by an in-memory database and TMDB fixtures in tests.
+
+use std::sync::mpsc;
+
+use crate::{
+    config::{AppConfig, EmailsConfig, NotificationsConfig, SMTPConfig, StateFilePath, TMDBConfig},
+    context::AppContext,
+    db::{self, Db},
+    tmdb::{self, EpisodeDetails, EpisodeId, EpisodeType, SeriesDetails, SeriesId, SeriesStatus},
+};
+
+pub fn make_config() -> AppConfig {
+    AppConfig {
+        state_file_path: StateFilePath(":memory:".to_owned()),
+        tmdb: TMDBConfig {
+            api_key: "test-api-key".to_owned(),
+            api_access_token: "test-api-access-token".to_owned(),
+            api_base_url: TMDBConfig::default_api_base_url(),
+            timeout_secs: TMDBConfig::default_timeout_secs(),
+            max_attempts: TMDBConfig::default_max_attempts(),
+            retry_delay_ms: TMDBConfig::default_retry_delay_ms(),
+            max_retry_after_secs: TMDBConfig::default_max_retry_after_secs(),
+            max_requests_per_second: TMDBConfig::default_max_requests_per_second(),
+        },
+        smtp: SMTPConfig {
+            host: "localhost".to_owned(),
+            port: 25,
+            user: "test".to_owned(),
+            password: "test".to_owned(),
+        },
+        emails: EmailsConfig {
+            from_name: Some("TVTrack".to_owned()),
+            from_address: "tvtrack@example.com".to_owned(),
+        },
+        notifications: NotificationsConfig::default(),
+        webhook: None,
+        backup: None,
+    }
+}
+
+/// The context uses `tmdb::FixtureClient` by default; tests can replace `ctx.tmdb` to serve different responses.
+pub fn make_context() -> AppContext {
+    let config = make_config();
+    let db = Db::open(&config.state_file_path.0).unwrap();
+    let tmdb = Box::new(tmdb::FixtureClient::new());
+    AppContext { config, db, tmdb }
+}
+
+pub fn make_episode(
+    id: i32,
+    season_number: i32,
+    episode_number: i32,
+    air_date: &str,
+) -> EpisodeDetails {
+    EpisodeDetails {
+        id: EpisodeId(id),
+        season_number,
+        episode_number,
+        name: format!("Episode {episode_number}"),
+        episode_type: EpisodeType::Standard,
+        air_date: air_date.parse::<chrono::NaiveDate>().unwrap().into(),
+    }
+}
+
+pub fn make_series_details(id: i32, name: &str) -> SeriesDetails {
+    SeriesDetails {
+        id: SeriesId(id),
+        name: name.to_owned(),
+        first_air_date: "2024-01-01".parse::<chrono::NaiveDate>().unwrap().into(),
+        number_of_seasons: 1,
+        number_of_episodes: 8,
+        last_episode_to_air: Some(make_episode(id * 100 + 1, 1, 1, "2024-01-01")),
+        next_episode_to_air: Some(make_episode(id * 100 + 2, 1, 2, "2024-01-08")),
+        status: SeriesStatus::ReturningSeries,
+        in_production: true,
+        poster_path: format!("/poster{id}.jpg"),
+    }
+}
+
+pub fn insert_user(ctx: &mut AppContext, name: &str, email: &str) -> db::User {
+    ctx.db.insert_user(name, email).unwrap()
+}
+
+/// Inserts the series along with a dummy poster, and subscribes the given users to it.
+pub fn insert_series(
+    ctx: &mut AppContext,
+    details: SeriesDetails,
+    users: &[&db::User],
+) -> db::Series {
+    ctx.db
+        .conn
+        .execute(
+            "INSERT INTO posters (img_data, mime_type, source_url) VALUES (?, 'image/jpeg', ?)",
+            (
+                format!("poster of {}", details.name).into_bytes(),
+                format!("https://image.tmdb.org/t/p/w92{}", details.poster_path),
+            ),
+        )
+        .unwrap();
+    let poster_id = db::PosterId(ctx.db.conn.last_insert_rowid());
+
+    let series = db::Series {
+        tmdb_id: details.id,
+        poster_id,
+        title: details.name.clone(),
+        first_air_date: details.first_air_date,
+        status: details.status,
+        in_production: details.in_production,
+        last_episode_air_date: details.last_episode_date(),
+        next_episode_air_date: details.next_episode_date(),
+        details_json: serde_json::to_value(&details).unwrap(),
+        details,
+        external_ids: tmdb::ExternalIds::default(),
+        update_timestamp: "2024-01-01T12:00:00Z".parse().unwrap(),
+        orphaned_timestamp: None,
+    };
+    ctx.db.insert_series(&series).unwrap();
+
+    for user in users {
+        ctx.db
+            .insert_tracked_series(user.id, series.tmdb_id, series.update_timestamp)
+            .unwrap();
+    }
+
+    series
+}
+
+/// Makes every `operation` (INSERT, UPDATE or DELETE) on the table fail from now on,
+/// to check that a failure halfway through a change leaves the database untouched.
+pub fn inject_failure(ctx: &mut AppContext, operation: &str, table: &str) {
+    ctx.db
+        .conn
+        .execute_batch(&format!(
+            "CREATE TEMP TRIGGER fail_{operation}_{table} BEFORE {operation} ON {table} BEGIN SELECT RAISE(ABORT, 'injected failure'); END;"
+        ))
+        .unwrap();
+}
+
+pub fn count_rows(ctx: &mut AppContext, table: &str) -> i64 {
+    ctx.db
+        .conn
+        .query_row(&format!("SELECT COUNT(*) FROM {table}"), (), |row| {
+            row.get(0)
+        })
+        .unwrap()
+}
+
+/// Fetches the details of the series from the TMDB fixtures, and turns them back to how they were a week before:
+/// the previous episode is the last one that aired, and the current last episode is the next one to air.
+pub fn make_outdated_series_details(ctx: &mut AppContext, id: SeriesId) -> SeriesDetails {
+    let mut details = ctx.tmdb.get_series_details(id).unwrap();
+    let last_ep = details.last_episode_to_air.take().unwrap();
+    let prev_ep = EpisodeDetails {
+        id: EpisodeId(last_ep.id.0 - 3),
+        season_number: last_ep.season_number,
+        episode_number: last_ep.episode_number - 3,
+        name: "Previous Episode".to_owned(),
+        episode_type: EpisodeType::Standard,
+        air_date: (last_ep.air_date.0.unwrap() - chrono::Days::new(7)).into(),
+    };
+
+    details.last_episode_to_air = Some(prev_ep);
+    details.next_episode_to_air = Some(last_ep);
+    details.number_of_episodes -= 3;
+    details
+}
+
+pub struct ReceivedRequest {
+    pub url: String,
+    pub headers: Vec<tiny_http::Header>,
+    pub body: String,
+}
+
+impl ReceivedRequest {
+    pub fn header(&self, name: &str) -> Option<String> {
+        self.headers
+            .iter()
+            .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name))
+            .map(|h| h.value.to_string())
+    }
+}
+
+/// Starts a local HTTP server which responds to consecutive requests with the given status codes.
+/// Returns the root URL of the server and a channel receiving the requests.
+pub fn start_http_server(statuses: Vec<u16>) -> (String, mpsc::Receiver<ReceivedRequest>) {
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        for status in statuses {
+            let mut request = server.recv().unwrap();
+            let mut body = String::new();
+            request.as_reader().read_to_string(&mut body).unwrap();
+            sender
+                .send(ReceivedRequest {
+                    url: request.url().to_owned(),
+                    headers: request.headers().to_vec(),
+                    body,
+                })
+                .unwrap();
+            request.respond(tiny_http::Response::empty(status)).unwrap();
+        }
+    });
+
+    (url, receiver)
+}
@@ -0,0 +1,39 @@
+use super::{
+    ExternalIds, ExternalSource, Poster, SearchResults, SeasonDetails, SeriesDetails, SeriesFound,
+    SeriesId,
+};
+
+/// The parts of the TMDB API that we use.
+/// Implemented by `Client` for the real thing, and by `FixtureClient` in tests.
+pub trait Api {
+    fn make_series_url(&self, id: SeriesId) -> String {
+        format!("https://www.themoviedb.org/tv/{id}")
+    }
+
+    fn get_poster(&mut self, path: &str) -> anyhow::Result<Poster>;
+
+    /// `page` starts at 1.
+    fn search_series(
+        &mut self,
+        title: &str,
+        first_air_year: Option<i32>,
+        page: i32,
+    ) -> anyhow::Result<SearchResults<SeriesFound>>;
+
+    /// Looks up the series that have the given ID in another database. There should normally be at most one.
+    fn find_by_external_id(
+        &mut self,
+        source: ExternalSource,
+        external_id: &str,
+    ) -> anyhow::Result<Vec<SeriesFound>>;
+
+    fn get_series_details(&mut self, id: SeriesId) -> anyhow::Result<SeriesDetails>;
+
+    fn get_external_ids(&mut self, id: SeriesId) -> anyhow::Result<ExternalIds>;
+
+    fn get_season_details(
+        &mut self,
+        id: SeriesId,
+        season_number: i32,
+    ) -> anyhow::Result<SeasonDetails>;
+}
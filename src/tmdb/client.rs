@@ -1,56 +1,151 @@
-use std::{io::Read, path::Path};
+use std::{io::Read, path::Path, time::Duration};
 
 use anyhow::{Context, anyhow};
 use lettre::message::header::ContentType;
 
-use super::{MimeType, Poster, SearchResults, SeriesDetails, SeriesFound, SeriesId};
-
-static API_ROOT_URL: &str = "https://api.themoviedb.org/3/";
+use super::{
+    Api, ExternalIds, ExternalSource, FindResults, MimeType, NotFoundError, Poster, SearchResults,
+    SeasonDetails, SeriesDetails, SeriesFound, SeriesId, rate_limiter::RateLimiter,
+};
+use crate::config::TMDBConfig;
 
 pub struct Client {
     agent: ureq::Agent,
+    api_base_url: String,
     #[allow(dead_code)]
     api_key: String,
     api_access_token: String,
+    max_attempts: u32,
+    retry_delay: Duration,
+    max_retry_after: Duration,
+    rate_limiter: RateLimiter,
+}
+
+/// Parses the value of a `Retry-After` header, which is either a number of seconds or an HTTP date.
+fn parse_retry_after(value: &str, now: chrono::DateTime<chrono::Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let retry_at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    // a date in the past means we can retry right away
+    Some((retry_at.to_utc() - now).to_std().unwrap_or(Duration::ZERO))
 }
 
 impl Client {
-    pub fn new(api_key: String, api_access_token: String) -> Client {
+    pub fn new(config: &TMDBConfig) -> Client {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(config.timeout_secs)))
+            // we want to look at the headers of error responses, see `call`
+            .http_status_as_error(false)
+            .build()
+            .into();
+
         Client {
-            agent: ureq::Agent::new_with_defaults(),
-            api_key,
-            api_access_token,
+            agent,
+            api_base_url: config.api_base_url.trim_end_matches('/').to_owned(),
+            api_key: config.api_key.clone(),
+            api_access_token: config.api_access_token.clone(),
+            max_attempts: config.max_attempts.max(1),
+            retry_delay: Duration::from_millis(config.retry_delay_ms),
+            max_retry_after: Duration::from_secs(config.max_retry_after_secs),
+            rate_limiter: RateLimiter::new(config.max_requests_per_second),
         }
     }
 
-    fn get(&mut self, path: &str) -> ureq::RequestBuilder<ureq::typestate::WithoutBody> {
-        self.agent.get(&format!("{API_ROOT_URL}{path}")).header(
-            "Authorization",
-            &format!("Bearer {}", self.api_access_token),
-        )
+    /// Exponential backoff with jitter: somewhere between half and all of the retry delay doubled for every previous retry.
+    fn backoff_delay(&self, attempt: u32) -> Duration {
+        let max_delay = self.retry_delay * 2u32.saturating_pow(attempt - 1);
+        max_delay.mul_f64(0.5 + fastrand::f64() / 2.0)
+    }
+
+    /// Makes a GET request, respecting the rate limit, and retrying network errors, HTTP 5xx and 429 responses.
+    /// Gives up right away if the server asks to retry later than `max_retry_after`.
+    fn call(
+        &mut self,
+        url: &str,
+        query: &[(&str, &str)],
+        accept: Option<&str>,
+    ) -> anyhow::Result<ureq::Body> {
+        let mut attempt = 1;
+
+        loop {
+            self.rate_limiter.wait();
+
+            let mut request = self.agent.get(url).header(
+                "Authorization",
+                &format!("Bearer {}", self.api_access_token),
+            );
+            for (key, value) in query {
+                request = request.query(key, value);
+            }
+            if let Some(accept) = accept {
+                request = request.header("Accept", accept);
+            }
+
+            let (err, retry_after) = match request.call() {
+                Ok(response) if response.status().is_success() => {
+                    return Ok(response.into_body());
+                }
+                Ok(response) => {
+                    let status = response.status().as_u16();
+                    if status == 404 {
+                        return Err(NotFoundError {
+                            url: url.to_owned(),
+                        }
+                        .into());
+                    }
+
+                    let err = anyhow!("GET {url}: HTTP status {status}");
+                    if status != 429 && status < 500 {
+                        return Err(err);
+                    }
+
+                    let retry_after = response
+                        .headers()
+                        .get("Retry-After")
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| parse_retry_after(value, chrono::Utc::now()));
+                    (err, retry_after)
+                }
+                Err(err) => (anyhow!(err).context(format!("GET {url}")), None),
+            };
+
+            if attempt >= self.max_attempts {
+                return Err(err.context(format!("Giving up after {attempt} attempts")));
+            }
+
+            if let Some(retry_after) = retry_after.filter(|&delay| delay > self.max_retry_after) {
+                return Err(err.context(format!(
+                    "Giving up after {attempt} attempts: asked to retry in {retry_after:?}, longer than the maximum of {:?}",
+                    self.max_retry_after
+                )));
+            }
+
+            let delay = retry_after.unwrap_or_else(|| self.backoff_delay(attempt));
+            log::warn!("TMDB request attempt {attempt} failed, retrying in {delay:?}: {err:#}");
+            std::thread::sleep(delay);
+            attempt += 1;
+        }
     }
 
-    pub fn make_series_url(&self, id: SeriesId) -> String {
-        format!("https://www.themoviedb.org/tv/{id}")
+    fn get(&mut self, path: &str, query: &[(&str, &str)]) -> anyhow::Result<String> {
+        let url = format!("{}/{path}", self.api_base_url);
+        Ok(self.call(&url, query, None)?.read_to_string()?)
     }
+}
 
-    pub fn get_poster(&self, path: &str) -> anyhow::Result<Poster> {
+impl Api for Client {
+    fn get_poster(&mut self, path: &str) -> anyhow::Result<Poster> {
         // TODO: we should be getting the base url and the image width closest to what we want from the TMDB API; see https://developer.themoviedb.org/docs/image-basics
         // TODO: use bigger posters, like w154 or w185, and in the email, align them below the titles
         let url = format!("https://image.tmdb.org/t/p/w92{path}");
         let mime_type = MimeType::identify_from_ext(path)?;
 
         let mut buf = vec![];
-        self.agent
-            .get(&url)
-            .header(
-                "Authorization",
-                &format!("Bearer {}", self.api_access_token),
-            )
-            .header("Accept", mime_type.as_str())
-            .call()
+        self.call(&url, &[], Some(mime_type.as_str()))
             .with_context(|| format!("TMDB::get_poster({path:?})"))?
-            .into_body()
             .into_reader()
             .read_to_end(&mut buf)?;
 
@@ -61,43 +156,59 @@ impl Client {
         })
     }
 
-    pub fn search_series(
+    fn search_series(
         &mut self,
         title: &str,
         first_air_year: Option<i32>,
+        page: i32,
     ) -> anyhow::Result<SearchResults<SeriesFound>> {
         let result_json = {
-            let mut query = self
-                .get("search/tv")
-                .query("query", title)
-                .query("page", "1");
-
-            if let Some(year) = first_air_year {
-                query = query.query("first_air_date_year", year.to_string());
+            let year = first_air_year.map(|year| year.to_string());
+            let page = page.to_string();
+            let mut query = vec![("query", title), ("page", &page)];
+            if let Some(year) = &year {
+                query.push(("first_air_date_year", year));
             }
 
-            query
-                .call()
-                .with_context(|| format!("TMDB::search_series({title:?}, {first_air_year:?})"))?
-                .into_body()
-                .read_to_string()?
+            self.get("search/tv", &query).with_context(|| {
+                format!("TMDB::search_series({title:?}, {first_air_year:?}, {page})")
+            })?
         };
 
         serde_json::from_str::<SearchResults<SeriesFound>>(&result_json).with_context(|| {
             format!(
-                "TMDB::search_series({title:?}, {first_air_year:?}) JSON parse error: {}",
+                "TMDB::search_series({title:?}, {first_air_year:?}, {page}) JSON parse error: {}",
                 &result_json
             )
         })
     }
 
-    pub fn get_series_details(&mut self, id: SeriesId) -> anyhow::Result<SeriesDetails> {
+    fn find_by_external_id(
+        &mut self,
+        source: ExternalSource,
+        external_id: &str,
+    ) -> anyhow::Result<Vec<SeriesFound>> {
+        source.validate_id(external_id)?;
         let result_json = self
-            .get(&format!("tv/{id}"))
-            .call()
-            .with_context(|| format!("TMDB::get_series_details({id})"))?
-            .into_body()
-            .read_to_string()?;
+            .get(
+                &format!("find/{external_id}"),
+                &[("external_source", source.api_name())],
+            )
+            .with_context(|| format!("TMDB::find_by_external_id({source}, {external_id:?})"))?;
+
+        let results = serde_json::from_str::<FindResults>(&result_json).with_context(|| {
+            format!(
+                "TMDB::find_by_external_id({source}, {external_id:?}) JSON parse error: {}",
+                &result_json
+            )
+        })?;
+        Ok(results.tv_results)
+    }
+
+    fn get_series_details(&mut self, id: SeriesId) -> anyhow::Result<SeriesDetails> {
+        let result_json = self
+            .get(&format!("tv/{id}"), &[])
+            .with_context(|| format!("TMDB::get_series_details({id})"))?;
 
         serde_json::from_str::<SeriesDetails>(&result_json).with_context(|| {
             format!(
@@ -106,4 +217,57 @@ impl Client {
             )
         })
     }
+
+    fn get_external_ids(&mut self, id: SeriesId) -> anyhow::Result<ExternalIds> {
+        let result_json = self
+            .get(&format!("tv/{id}/external_ids"), &[])
+            .with_context(|| format!("TMDB::get_external_ids({id})"))?;
+
+        serde_json::from_str::<ExternalIds>(&result_json).with_context(|| {
+            format!(
+                "TMDB::get_external_ids({id}) JSON parse error: {}",
+                &result_json
+            )
+        })
+    }
+
+    fn get_season_details(
+        &mut self,
+        id: SeriesId,
+        season_number: i32,
+    ) -> anyhow::Result<SeasonDetails> {
+        let result_json = self
+            .get(&format!("tv/{id}/season/{season_number}"), &[])
+            .with_context(|| format!("TMDB::get_season_details({id}, {season_number})"))?;
+
+        serde_json::from_str::<SeasonDetails>(&result_json).with_context(|| {
+            format!(
+                "TMDB::get_season_details({id}, {season_number}) JSON parse error: {}",
+                &result_json
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_retry_after_seconds_and_dates() {
+        let now = "2024-05-10T06:00:00Z".parse().unwrap();
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Fri, 10 May 2024 06:00:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Fri, 10 May 2024 05:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
 }
@@ -41,6 +41,9 @@ impl FromSql for EpisodeId {
 pub enum EpisodeType {
     #[default]
     Standard,
+    #[serde(rename = "mid_season")]
+    #[strum(to_string = "Mid-season")]
+    MidSeason,
     Finale,
 }
 
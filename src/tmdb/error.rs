@@ -0,0 +1,20 @@
+use std::fmt;
+
+/// The requested resource doesn't exist on TMDB (HTTP 404), e.g. because the series was deleted or merged into another one.
+#[derive(Debug)]
+pub struct NotFoundError {
+    pub url: String,
+}
+
+impl fmt::Display for NotFoundError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Not found on TMDB: {}", self.url)
+    }
+}
+
+impl std::error::Error for NotFoundError {}
+
+/// Whether the error is, or was caused by, a `NotFoundError`.
+pub fn is_not_found(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| cause.is::<NotFoundError>())
+}
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// IDs of a series in other databases, as returned by `tv/{id}/external_ids`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExternalIds {
+    pub imdb_id: Option<String>,
+    pub tvdb_id: Option<i64>,
+    pub wikidata_id: Option<String>,
+}
+
+impl ExternalIds {
+    pub fn imdb_url(&self) -> Option<String> {
+        self.imdb_id
+            .as_ref()
+            .map(|id| format!("https://www.imdb.com/title/{id}/"))
+    }
+
+    pub fn tvdb_url(&self) -> Option<String> {
+        self.tvdb_id
+            .map(|id| format!("https://thetvdb.com/dereferrer/series/{id}"))
+    }
+
+    /// Names and URLs of the pages of the series on the sites we know an ID for.
+    pub fn links(&self) -> Vec<(&'static str, String)> {
+        [("IMDb", self.imdb_url()), ("TVDB", self.tvdb_url())]
+            .into_iter()
+            .filter_map(|(name, url)| url.map(|url| (name, url)))
+            .collect()
+    }
+}
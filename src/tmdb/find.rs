@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+
+use super::SeriesFound;
+
+/// Other databases whose IDs TMDB can look up series by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString, clap::ValueEnum)]
+#[strum(serialize_all = "lowercase")]
+pub enum ExternalSource {
+    Imdb,
+    Tvdb,
+    Wikidata,
+}
+
+impl ExternalSource {
+    /// The value of the `external_source` query parameter of the find API.
+    pub fn api_name(self) -> &'static str {
+        match self {
+            ExternalSource::Imdb => "imdb_id",
+            ExternalSource::Tvdb => "tvdb_id",
+            ExternalSource::Wikidata => "wikidata_id",
+        }
+    }
+
+    /// Checks that the ID has the format of this source (e.g. tt13016388 for IMDb), so that it can safely be put in
+    /// the path of an API URL.
+    pub fn validate_id(self, id: &str) -> anyhow::Result<()> {
+        let number = match self {
+            ExternalSource::Imdb => id.strip_prefix("tt"),
+            ExternalSource::Tvdb => Some(id),
+            ExternalSource::Wikidata => id.strip_prefix('Q'),
+        };
+        if number
+            .is_none_or(|number| number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()))
+        {
+            anyhow::bail!("Invalid {self} ID: {id:?}");
+        }
+        Ok(())
+    }
+}
+
+/// We only care about series, but the response also includes movies, people, seasons and episodes.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FindResults {
+    pub tv_results: Vec<SeriesFound>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_ids_by_source() {
+        ExternalSource::Imdb.validate_id("tt13016388").unwrap();
+        ExternalSource::Tvdb.validate_id("411384").unwrap();
+        ExternalSource::Wikidata.validate_id("Q98487963").unwrap();
+
+        for (source, id) in [
+            (ExternalSource::Imdb, "13016388"),
+            (ExternalSource::Imdb, "tt"),
+            (ExternalSource::Imdb, "tt1/../../tv/1"),
+            (ExternalSource::Tvdb, "tt13016388"),
+            (ExternalSource::Tvdb, "411384?api_key=x"),
+            (ExternalSource::Wikidata, "98487963"),
+            (ExternalSource::Wikidata, "Q 1"),
+        ] {
+            assert!(source.validate_id(id).is_err(), "{source}:{id}");
+        }
+    }
+}
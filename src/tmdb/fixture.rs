@@ -0,0 +1,242 @@
+//! An offline stand-in for the TMDB API for tests, serving the example responses recorded in `tmdb-api-docs/`.
+//! Anything else is reported as not found.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use chrono::Datelike;
+
+use super::{
+    Api, ExternalIds, ExternalSource, FindResults, MimeType, NotFoundError, Poster, SearchResults,
+    SeasonDetails, SeriesDetails, SeriesFound, SeriesId,
+};
+
+static FIXTURES: &[&str] = &[
+    include_str!("../../tmdb-api-docs/find-by-external-id.txt"),
+    include_str!("../../tmdb-api-docs/search-tv.txt"),
+    include_str!("../../tmdb-api-docs/tv-season-details.txt"),
+    include_str!("../../tmdb-api-docs/tv-series-details-3BodyProblem.txt"),
+    include_str!("../../tmdb-api-docs/tv-series-details-Elsbeth.txt"),
+];
+
+/// Splits a fixture file into the path and query of the request (relative to the API root), and the response JSON.
+/// The files are abbreviated in places with `...`, which we just drop.
+fn parse_fixture(text: &str) -> anyhow::Result<(&str, HashMap<&str, &str>, String)> {
+    let url = text
+        .lines()
+        .find_map(|line| line.strip_prefix("GET https://api.themoviedb.org/3/"))
+        .context("Fixture without a GET line")?;
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let query = query
+        .split('&')
+        .filter_map(|kv| kv.split_once('='))
+        .collect();
+
+    let json_start = text
+        .find("\n{")
+        .context("Fixture without a JSON response")?;
+    let json = text[json_start..]
+        .lines()
+        .filter(|line| !matches!(line.trim(), "..." | "...,"))
+        .map(|line| line.replace("[...]", "[]"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok((path, query, json))
+}
+
+pub struct FixtureClient {
+    search_results: HashMap<(String, i32), SearchResults<SeriesFound>>,
+    find_results: HashMap<(String, String), Vec<SeriesFound>>,
+    series_details: HashMap<SeriesId, SeriesDetails>,
+    season_details: HashMap<(SeriesId, i32), SeasonDetails>,
+    external_ids: HashMap<SeriesId, ExternalIds>,
+}
+
+impl FixtureClient {
+    pub fn new() -> FixtureClient {
+        let mut client = FixtureClient {
+            search_results: HashMap::new(),
+            find_results: HashMap::new(),
+            series_details: HashMap::new(),
+            season_details: HashMap::new(),
+            external_ids: HashMap::new(),
+        };
+
+        for fixture in FIXTURES {
+            let (path, query, json) = parse_fixture(fixture).unwrap();
+            let segments: Vec<_> = path.split('/').collect();
+            match segments[..] {
+                ["search", "tv"] => {
+                    client = client
+                        .with_search_results(query["query"], serde_json::from_str(&json).unwrap());
+                }
+                ["find", external_id] => {
+                    let source = match query["external_source"] {
+                        "imdb_id" => ExternalSource::Imdb,
+                        "tvdb_id" => ExternalSource::Tvdb,
+                        "wikidata_id" => ExternalSource::Wikidata,
+                        other => panic!("Unsupported external source in fixture: {other}"),
+                    };
+                    let results: FindResults = serde_json::from_str(&json).unwrap();
+                    if source == ExternalSource::Imdb {
+                        for series in results.tv_results.iter() {
+                            let external_ids = ExternalIds {
+                                imdb_id: Some(external_id.to_owned()),
+                                ..Default::default()
+                            };
+                            client = client.with_external_ids(series.id, external_ids);
+                        }
+                    }
+                    client = client.with_find_results(source, external_id, results.tv_results);
+                }
+                ["tv", _] => {
+                    client = client.with_series_details(serde_json::from_str(&json).unwrap());
+                }
+                ["tv", id, "season", _] => {
+                    client = client.with_season_details(
+                        SeriesId(id.parse().unwrap()),
+                        serde_json::from_str(&json).unwrap(),
+                    );
+                }
+                _ => panic!("Unsupported fixture: {path}"),
+            }
+        }
+
+        client
+    }
+
+    /// The page is taken from `results.page`.
+    pub fn with_search_results(
+        mut self,
+        title: &str,
+        results: SearchResults<SeriesFound>,
+    ) -> FixtureClient {
+        self.search_results
+            .insert((title.to_lowercase(), results.page), results);
+        self
+    }
+
+    pub fn with_find_results(
+        mut self,
+        source: ExternalSource,
+        external_id: &str,
+        results: Vec<SeriesFound>,
+    ) -> FixtureClient {
+        self.find_results.insert(
+            (source.api_name().to_owned(), external_id.to_owned()),
+            results,
+        );
+        self
+    }
+
+    pub fn with_series_details(mut self, details: SeriesDetails) -> FixtureClient {
+        self.series_details.insert(details.id, details);
+        self
+    }
+
+    pub fn with_external_ids(mut self, id: SeriesId, external_ids: ExternalIds) -> FixtureClient {
+        self.external_ids.insert(id, external_ids);
+        self
+    }
+
+    pub fn with_season_details(mut self, id: SeriesId, season: SeasonDetails) -> FixtureClient {
+        self.season_details
+            .insert((id, season.season_number), season);
+        self
+    }
+}
+
+impl Default for FixtureClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Api for FixtureClient {
+    fn get_poster(&mut self, path: &str) -> anyhow::Result<Poster> {
+        Ok(Poster {
+            img_data: format!("poster {path}").into_bytes().into_boxed_slice(),
+            mime_type: MimeType::identify_from_ext(path)?,
+            source_url: format!("https://image.tmdb.org/t/p/w92{path}"),
+        })
+    }
+
+    fn search_series(
+        &mut self,
+        title: &str,
+        first_air_year: Option<i32>,
+        page: i32,
+    ) -> anyhow::Result<SearchResults<SeriesFound>> {
+        let mut results = self
+            .search_results
+            .get(&(title.to_lowercase(), page))
+            .cloned()
+            .unwrap_or(SearchResults {
+                page,
+                results: Vec::new(),
+                total_pages: 0,
+                total_results: 0,
+            });
+
+        if let Some(year) = first_air_year {
+            results
+                .results
+                .retain(|sr| sr.first_air_date.0.is_some_and(|dt| dt.year() == year));
+        }
+        Ok(results)
+    }
+
+    fn find_by_external_id(
+        &mut self,
+        source: ExternalSource,
+        external_id: &str,
+    ) -> anyhow::Result<Vec<SeriesFound>> {
+        // like the real client, which can't look up malformed IDs
+        source.validate_id(external_id)?;
+        Ok(self
+            .find_results
+            .get(&(source.api_name().to_owned(), external_id.to_owned()))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn get_series_details(&mut self, id: SeriesId) -> anyhow::Result<SeriesDetails> {
+        match self.series_details.get(&id) {
+            Some(details) => Ok(details.clone()),
+            None => Err(NotFoundError {
+                url: format!("fixture:tv/{id}"),
+            }
+            .into()),
+        }
+    }
+
+    /// Series with details but without external IDs have none.
+    fn get_external_ids(&mut self, id: SeriesId) -> anyhow::Result<ExternalIds> {
+        match (
+            self.external_ids.get(&id),
+            self.series_details.contains_key(&id),
+        ) {
+            (Some(external_ids), _) => Ok(external_ids.clone()),
+            (None, true) => Ok(ExternalIds::default()),
+            (None, false) => Err(NotFoundError {
+                url: format!("fixture:tv/{id}/external_ids"),
+            }
+            .into()),
+        }
+    }
+
+    fn get_season_details(
+        &mut self,
+        id: SeriesId,
+        season_number: i32,
+    ) -> anyhow::Result<SeasonDetails> {
+        match self.season_details.get(&(id, season_number)) {
+            Some(season) => Ok(season.clone()),
+            None => Err(NotFoundError {
+                url: format!("fixture:tv/{id}/season/{season_number}"),
+            }
+            .into()),
+        }
+    }
+}
@@ -1,17 +1,32 @@
 #![allow(unused_imports, unused_import_braces)]
 
+mod api;
 mod client;
 mod episode;
+mod error;
+mod external_ids;
+mod find;
+#[cfg(test)]
+mod fixture;
 mod mime_type;
 mod optional_date;
 mod poster;
+mod rate_limiter;
 mod search;
+mod season;
 mod series;
 
+pub use api::Api;
 pub use client::Client;
 pub use episode::{EpisodeDetails, EpisodeId, EpisodeType};
+pub use error::{NotFoundError, is_not_found};
+pub use external_ids::ExternalIds;
+pub use find::{ExternalSource, FindResults};
+#[cfg(test)]
+pub use fixture::FixtureClient;
 pub use mime_type::MimeType;
 pub use optional_date::OptionalDate;
 pub use poster::Poster;
 pub use search::{SearchResults, SeriesFound};
+pub use season::SeasonDetails;
 pub use series::{SeriesDetails, SeriesId, SeriesStatus};
@@ -0,0 +1,32 @@
+use std::time::{Duration, Instant};
+
+/// Spaces out requests evenly so that no more than `max_per_second` of them are made per second.
+pub struct RateLimiter {
+    min_interval: Duration,
+    next_allowed: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// A `max_per_second` of 0 means no limit.
+    pub fn new(max_per_second: u32) -> RateLimiter {
+        RateLimiter {
+            min_interval: if max_per_second == 0 {
+                Duration::ZERO
+            } else {
+                Duration::from_secs(1) / max_per_second
+            },
+            next_allowed: None,
+        }
+    }
+
+    /// Blocks until the next request is allowed to be made.
+    pub fn wait(&mut self) {
+        if let Some(next_allowed) = self.next_allowed {
+            let now = Instant::now();
+            if next_allowed > now {
+                std::thread::sleep(next_allowed - now);
+            }
+        }
+        self.next_allowed = Some(Instant::now() + self.min_interval);
+    }
+}
@@ -20,4 +20,7 @@ pub struct SeriesFound {
 
     pub first_air_date: OptionalDate,
     pub popularity: f64,
-}
\ No newline at end of file
+
+    #[serde(default)]
+    pub origin_country: Vec<String>,
+}
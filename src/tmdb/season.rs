@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+use super::{EpisodeDetails, OptionalDate};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SeasonDetails {
+    pub id: i32,
+    pub season_number: i32,
+
+    pub name: String,
+    pub air_date: OptionalDate,
+
+    pub episodes: Vec<EpisodeDetails>,
+}
@@ -27,13 +27,23 @@ impl FromSql for SeriesId {
     }
 }
 
+/// On the command line, the variants are written in kebab-case, e.g. `returning-series`.
 #[derive(
-    Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString, strum::VariantNames,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    strum::Display,
+    strum::EnumString,
+    strum::VariantNames,
+    clap::ValueEnum,
 )]
 pub enum SeriesStatus {
     /// This seems to be used to mean yet-unreleased series only.
     /// Note that there's also `SeriesDetails::in_production`
     #[strum(to_string = "In Production")]
+    #[value(help = "Not released yet")]
     InProduction,
 
     #[strum(to_string = "Returning Series")]
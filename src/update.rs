@@ -5,7 +5,8 @@ use crate::{db, tmdb};
 
 use super::{AppContext, EpisodeDetails, SeriesDetails, SeriesStatus};
 
-#[derive(Debug)]
+/// Stored as JSON in the history of the series, see `db::SeriesHistoryEntry`.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct SeriesDetailsChanges {
     pub id: tmdb::SeriesId,
     pub in_production_change: Option<(bool, bool)>,
@@ -13,7 +14,20 @@ pub struct SeriesDetailsChanges {
     pub last_episode_change: Option<(Option<EpisodeDetails>, Option<EpisodeDetails>)>,
     pub next_episode_change: Option<(Option<EpisodeDetails>, Option<EpisodeDetails>)>,
     pub episode_count_change: Option<(i32, i32)>,
-    // TODO: we should also check for episodes that have aired, as next_episode_to_air may change in bulk (e.g. on Netflix where a whole season is released all at once)
+
+    /// All episodes that aired since the previous update, in order.
+    /// There can be many of them at once, e.g. on Netflix where a whole season is released at the same time.
+    pub aired_episodes: Vec<EpisodeDetails>,
+
+    /// Episodes that were added to or removed from the seasons known before the update, e.g. newly announced ones.
+    /// Missing from the history recorded before these were tracked.
+    #[serde(default)]
+    pub added_episodes: Vec<EpisodeDetails>,
+    #[serde(default)]
+    pub removed_episodes: Vec<EpisodeDetails>,
+
+    /// The series no longer exists on TMDB. None of the other changes are set in this case.
+    pub orphaned: bool,
 }
 
 impl SeriesDetailsChanges {
@@ -25,6 +39,10 @@ impl SeriesDetailsChanges {
             last_episode_change: None,
             next_episode_change: None,
             episode_count_change: None,
+            aired_episodes: Vec::new(),
+            added_episodes: Vec::new(),
+            removed_episodes: Vec::new(),
+            orphaned: false,
         }
     }
 
@@ -34,6 +52,10 @@ impl SeriesDetailsChanges {
             || self.last_episode_change.is_some()
             || self.next_episode_change.is_some()
             || self.episode_count_change.is_some()
+            || !self.aired_episodes.is_empty()
+            || !self.added_episodes.is_empty()
+            || !self.removed_episodes.is_empty()
+            || self.orphaned
     }
 
     pub fn summary(&self) -> String {
@@ -43,6 +65,9 @@ impl SeriesDetailsChanges {
 
         let mut summary = String::with_capacity(256);
         summary += "\n";
+        if self.orphaned {
+            summary += " - Removed from TMDB, no longer being updated\n";
+        }
         if let Some((old_in_prod, new_in_prod)) = self.in_production_change {
             summary += &format!(" - In production: {old_in_prod} => {new_in_prod}\n");
         }
@@ -78,6 +103,24 @@ impl SeriesDetailsChanges {
         if let Some((old_ep_count, new_ep_count)) = self.episode_count_change {
             summary += &format!(" - Episode count: {old_ep_count} => {new_ep_count}\n");
         }
+        if !self.aired_episodes.is_empty() {
+            summary += " - Aired episodes:\n";
+            for ep in self.aired_episodes.iter() {
+                summary += &format!("   - {}\n", ep.identify());
+            }
+        }
+        if !self.added_episodes.is_empty() {
+            summary += " - New episodes:\n";
+            for ep in self.added_episodes.iter() {
+                summary += &format!("   - {}\n", ep.identify());
+            }
+        }
+        if !self.removed_episodes.is_empty() {
+            summary += " - Removed episodes:\n";
+            for ep in self.removed_episodes.iter() {
+                summary += &format!("   - {}\n", ep.identify());
+            }
+        }
 
         summary
     }
@@ -127,40 +170,265 @@ fn collect_series_details_changes(
     changes
 }
 
+/// The seasons whose episode lists may have changed since the last update:
+/// from the season of the previous last episode up to the latest announced season.
+/// Earlier seasons that were never stored, e.g. because the series was added before seasons were tracked,
+/// are backfilled by starting from the first of them instead.
+fn seasons_to_refresh(
+    old_details: &SeriesDetails,
+    new_details: &SeriesDetails,
+    stored_season_numbers: &[i32],
+) -> std::ops::RangeInclusive<i32> {
+    // season 0 is for specials, which we only care about if they are the last episode
+    let first_season = old_details
+        .last_episode_to_air
+        .as_ref()
+        .map(|ep| ep.season_number)
+        .unwrap_or(1);
+
+    let last_season = [
+        &new_details.last_episode_to_air,
+        &new_details.next_episode_to_air,
+    ]
+    .into_iter()
+    .flatten()
+    .map(|ep| ep.season_number)
+    .fold(new_details.number_of_seasons, i32::max);
+
+    let first_missing_season = (1..first_season).find(|n| !stored_season_numbers.contains(n));
+
+    first_missing_season.unwrap_or(first_season)..=last_season
+}
+
+pub fn fetch_seasons(
+    ctx: &mut AppContext,
+    series_id: tmdb::SeriesId,
+    season_numbers: std::ops::RangeInclusive<i32>,
+) -> anyhow::Result<Vec<tmdb::SeasonDetails>> {
+    let mut seasons = Vec::new();
+    for season_number in season_numbers {
+        seasons.push(ctx.tmdb.get_season_details(series_id, season_number)?);
+    }
+    Ok(seasons)
+}
+
+/// Compares the stored episode list of a season with the newly fetched one,
+/// returning the episodes that were added and the ones that were removed.
+fn diff_episode_lists(
+    old_episodes: &[db::Episode],
+    new_episodes: &[EpisodeDetails],
+) -> (Vec<EpisodeDetails>, Vec<EpisodeDetails>) {
+    let added = new_episodes
+        .iter()
+        .filter(|new_ep| old_episodes.iter().all(|old_ep| old_ep.id != new_ep.id))
+        .cloned()
+        .collect();
+    let removed = old_episodes
+        .iter()
+        .filter(|old_ep| new_episodes.iter().all(|new_ep| new_ep.id != old_ep.id))
+        .map(db::Episode::to_details)
+        .collect();
+    (added, removed)
+}
+
+/// The episodes that were added to and removed from the stored seasons, see `diff_episode_lists`.
+/// Seasons that weren't stored before are skipped, as all their episodes would show up as added, which is just noise.
+fn collect_episode_list_changes(
+    db: &mut db::Db,
+    series_id: tmdb::SeriesId,
+    stored_season_numbers: &[i32],
+    seasons: &[tmdb::SeasonDetails],
+) -> anyhow::Result<(Vec<EpisodeDetails>, Vec<EpisodeDetails>)> {
+    let mut all_added = Vec::new();
+    let mut all_removed = Vec::new();
+    for season in seasons.iter() {
+        if !stored_season_numbers.contains(&season.season_number) {
+            continue;
+        }
+
+        let old_episodes = db.get_episodes_of_season(series_id, season.season_number)?;
+        let (added, removed) = diff_episode_lists(&old_episodes, &season.episodes);
+        all_added.extend(added);
+        all_removed.extend(removed);
+    }
+    Ok((all_added, all_removed))
+}
+
+pub fn store_seasons(
+    db: &mut db::Db,
+    series_id: tmdb::SeriesId,
+    seasons: &[tmdb::SeasonDetails],
+    update_timestamp: chrono::DateTime<chrono::Utc>,
+) -> anyhow::Result<()> {
+    for season in seasons.iter() {
+        db.upsert_season(series_id, season, update_timestamp)?;
+    }
+    Ok(())
+}
+
+/// Determines all episodes that aired after the old last episode, up to and including the new last episode.
+fn collect_aired_episodes(
+    old_details: &SeriesDetails,
+    new_details: &SeriesDetails,
+    seasons: &[tmdb::SeasonDetails],
+) -> Vec<EpisodeDetails> {
+    let Some(new_last_ep) = &new_details.last_episode_to_air else {
+        return Vec::new();
+    };
+
+    let episode_key = |ep: &EpisodeDetails| (ep.season_number, ep.episode_number);
+    let old_last_ep_key = old_details.last_episode_to_air.as_ref().map(episode_key);
+    let new_last_ep_key = episode_key(new_last_ep);
+
+    if old_last_ep_key.is_some_and(|old_key| old_key >= new_last_ep_key) {
+        return Vec::new();
+    }
+
+    let mut aired_episodes: Vec<_> = seasons
+        .iter()
+        .flat_map(|season| season.episodes.iter())
+        .filter(|ep| {
+            let key = episode_key(ep);
+            old_last_ep_key.is_none_or(|old_key| key > old_key) && key <= new_last_ep_key
+        })
+        .cloned()
+        .collect();
+
+    if aired_episodes.is_empty() {
+        aired_episodes.push(new_last_ep.clone());
+    }
+
+    aired_episodes
+}
+
 fn update_and_collect_changes(
     ctx: &mut AppContext,
     old_details: &SeriesDetails,
-) -> anyhow::Result<(
-    SeriesDetailsChanges,
-    SeriesDetails,
-    chrono::DateTime<chrono::Utc>,
-)> {
+    new_details: &SeriesDetails,
+    external_ids: &tmdb::ExternalIds,
+    dry_run: bool,
+) -> anyhow::Result<(SeriesDetailsChanges, chrono::DateTime<chrono::Utc>)> {
     let series_id = old_details.id;
-    let new_details = ctx.tmdb.get_series_details(series_id)?;
-    let changes = collect_series_details_changes(old_details, &new_details);
+    let stored_season_numbers: Vec<_> = ctx
+        .db
+        .get_seasons_of_series(series_id)?
+        .into_iter()
+        .map(|season| season.season_number)
+        .collect();
+    let season_numbers = seasons_to_refresh(old_details, new_details, &stored_season_numbers);
+    let seasons = fetch_seasons(ctx, series_id, season_numbers)?;
+
+    let mut changes = collect_series_details_changes(old_details, new_details);
+    changes.aired_episodes = collect_aired_episodes(old_details, new_details, &seasons);
+    (changes.added_episodes, changes.removed_episodes) =
+        collect_episode_list_changes(&mut ctx.db, series_id, &stored_season_numbers, &seasons)?;
     let update_timestamp = chrono::Utc::now();
 
-    ctx.db.conn.execute(
-        "UPDATE series SET status = :status, in_production = :in_production, last_episode_air_date = :last_episode_air_date, next_episode_air_date = :next_episode_air_date, details = :details, update_timestamp = :update_timestamp WHERE tmdb_id = :id",
-        rusqlite::named_params! {
-            ":id": series_id,
-            ":status": new_details.status,
-            ":in_production": new_details.in_production,
-            ":last_episode_air_date": new_details.last_episode_date(),
-            ":next_episode_air_date": new_details.next_episode_date(),
-            ":details": serde_json::to_value(&new_details).unwrap(),
-            ":update_timestamp": update_timestamp,
+    if dry_run {
+        log::debug!(
+            "Dry run: not updating series {} in the database",
+            old_details.identify()
+        );
+        return Ok((changes, update_timestamp));
+    }
+
+    // all or nothing, so that a failure halfway through doesn't leave e.g. the new details with the old episodes
+    ctx.db.in_transaction(|db| {
+        db.conn.execute(
+            "UPDATE series SET status = :status, in_production = :in_production, last_episode_air_date = :last_episode_air_date, next_episode_air_date = :next_episode_air_date, details = :details, update_timestamp = :update_timestamp, orphaned_timestamp = NULL, imdb_id = :imdb_id, tvdb_id = :tvdb_id, wikidata_id = :wikidata_id WHERE tmdb_id = :id",
+            rusqlite::named_params! {
+                ":id": series_id,
+                ":status": new_details.status,
+                ":in_production": new_details.in_production,
+                ":last_episode_air_date": new_details.last_episode_date(),
+                ":next_episode_air_date": new_details.next_episode_date(),
+                ":details": serde_json::to_value(new_details).unwrap(),
+                ":update_timestamp": update_timestamp,
+                ":imdb_id": external_ids.imdb_id,
+                ":tvdb_id": external_ids.tvdb_id,
+                ":wikidata_id": external_ids.wikidata_id,
+            }
+        ).with_context(|| format!("Updating series {} in the database", old_details.identify()))?;
+
+        store_seasons(db, series_id, &seasons, update_timestamp)?;
+
+        if changes.has_any_changes() {
+            db.insert_series_history(
+                series_id,
+                update_timestamp,
+                &serde_json::to_value(&changes).unwrap(),
+                old_details,
+            )?;
         }
-    ).with_context(|| format!("Updating series {} in the database", old_details.identify()))?;
+        Ok(())
+    })?;
 
-    Ok((changes, new_details, update_timestamp))
+    Ok((changes, update_timestamp))
+}
+
+/// Records that the series no longer exists on TMDB, reporting it as a change the first time around only.
+fn mark_orphaned(
+    ctx: &mut AppContext,
+    series: &mut db::Series,
+    dry_run: bool,
+) -> anyhow::Result<Option<SeriesDetailsChanges>> {
+    if let Some(orphaned_timestamp) = series.orphaned_timestamp {
+        log::warn!(
+            "Series {} still doesn't exist on TMDB (since {orphaned_timestamp}); use `relink` to link it to another TMDB series",
+            series.details.identify()
+        );
+        return Ok(None);
+    }
+
+    log::warn!(
+        "Series {} no longer exists on TMDB, marking it as orphaned; use `relink` to link it to another TMDB series",
+        series.details.identify()
+    );
+
+    let orphaned_timestamp = chrono::Utc::now();
+    let mut changes = SeriesDetailsChanges::new(series.tmdb_id);
+    changes.orphaned = true;
+
+    if !dry_run {
+        ctx.db.in_transaction(|db| {
+            db.conn
+                .execute(
+                    "UPDATE series SET orphaned_timestamp = ? WHERE tmdb_id = ?",
+                    (orphaned_timestamp, series.tmdb_id),
+                )
+                .with_context(|| {
+                    format!(
+                        "Marking series {} as orphaned in the database",
+                        series.details.identify()
+                    )
+                })?;
+            db.insert_series_history(
+                series.tmdb_id,
+                orphaned_timestamp,
+                &serde_json::to_value(&changes).unwrap(),
+                &series.details,
+            )
+        })?;
+    }
+    series.orphaned_timestamp = Some(orphaned_timestamp);
+
+    Ok(Some(changes))
 }
 
 pub fn update_one_series(
     ctx: &mut AppContext,
     series: &mut db::Series,
     force: bool,
+    dry_run: bool,
 ) -> anyhow::Result<Option<SeriesDetailsChanges>> {
+    if let (Some(orphaned_timestamp), false) = (series.orphaned_timestamp, force) {
+        log::info!(
+            "Not updating {} because it no longer exists on TMDB (since {orphaned_timestamp})",
+            series.details.identify()
+        );
+        return Ok(None);
+    }
+
     let (next_update_timestamp, reason) = determine_next_update_timestamp(series);
     if !force && chrono::Utc::now() < next_update_timestamp {
         log::info!(
@@ -171,8 +439,18 @@ pub fn update_one_series(
         return Ok(None);
     }
 
-    let (changes, new_details, update_timestamp) =
-        update_and_collect_changes(ctx, &series.details)?;
+    let new_details = match ctx.tmdb.get_series_details(series.tmdb_id) {
+        Ok(new_details) => new_details,
+        Err(err) if tmdb::is_not_found(&err) => return mark_orphaned(ctx, series, dry_run),
+        Err(err) => return Err(err),
+    };
+
+    let external_ids = ctx.tmdb.get_external_ids(series.tmdb_id)?;
+
+    let (changes, update_timestamp) =
+        update_and_collect_changes(ctx, &series.details, &new_details, &external_ids, dry_run)?;
+    // not something to notify anyone about
+    series.external_ids = external_ids;
     if !changes.has_any_changes() {
         log::info!(
             "No changes to {} since last update at {}",
@@ -224,12 +502,13 @@ pub fn determine_next_update_timestamp(
 pub fn update_all_series(
     ctx: &mut AppContext,
     force: bool,
+    dry_run: bool,
 ) -> anyhow::Result<Vec<(db::Series, SeriesDetailsChanges)>> {
     let series = ctx.db.get_all_series()?;
     let mut changes = Vec::with_capacity(series.len());
 
     for mut series in series.into_iter() {
-        match update_one_series(ctx, &mut series, force) {
+        match update_one_series(ctx, &mut series, force, dry_run) {
             Ok(None) => {}
             Ok(Some(series_changes)) => {
                 changes.push((series, series_changes));
@@ -242,3 +521,185 @@ pub fn update_all_series(
 
     Ok(changes)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn update_one_series_reports_and_stores_aired_episodes() {
+        let mut ctx = testing::make_context();
+        let alice = testing::insert_user(&mut ctx, "Alice", "alice@example.com");
+        let old_details = testing::make_outdated_series_details(&mut ctx, tmdb::SeriesId(108545));
+        let mut series = testing::insert_series(&mut ctx, old_details, &[&alice]);
+
+        let changes = update_one_series(&mut ctx, &mut series, true, false)
+            .unwrap()
+            .unwrap();
+
+        let aired: Vec<_> = changes
+            .aired_episodes
+            .iter()
+            .map(|ep| (ep.season_number, ep.episode_number))
+            .collect();
+        assert_eq!(aired, [(1, 6), (1, 7), (1, 8)]);
+        assert_eq!(changes.episode_count_change, Some((5, 8)));
+        assert!(changes.status_change.is_none());
+        assert!(matches!(
+            &changes.next_episode_change,
+            Some((Some(_), None))
+        ));
+
+        let stored = ctx.db.get_series_by_id(series.tmdb_id).unwrap().unwrap();
+        assert_eq!(stored.details.number_of_episodes, 8);
+        assert_eq!(stored.external_ids.imdb_id.as_deref(), Some("tt13016388"));
+        assert!(stored.next_episode_air_date.is_none());
+        assert_eq!(stored.update_timestamp, series.update_timestamp);
+        assert_eq!(
+            ctx.db.get_episodes_of_series(series.tmdb_id).unwrap().len(),
+            3
+        );
+
+        // nothing changes on the TMDB side, so there's nothing to report the second time around
+        assert!(
+            update_one_series(&mut ctx, &mut series, true, false)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn update_reports_episodes_added_to_and_removed_from_known_seasons() {
+        let mut ctx = testing::make_context();
+        let id = tmdb::SeriesId(108545);
+        let old_details = testing::make_outdated_series_details(&mut ctx, id);
+        let series = testing::insert_series(&mut ctx, old_details, &[]);
+
+        // the stored season is missing the finale, and has an episode that was dropped since
+        let mut season = ctx.tmdb.get_season_details(id, 1).unwrap();
+        let finale = season.episodes.pop().unwrap();
+        let mut dropped = season.episodes[0].clone();
+        dropped.id = tmdb::EpisodeId(1);
+        dropped.episode_number = 99;
+        dropped.name = "Dropped".to_owned();
+        season.episodes.push(dropped.clone());
+        ctx.db
+            .upsert_season(id, &season, series.update_timestamp)
+            .unwrap();
+
+        for dry_run in [true, false] {
+            let mut series = ctx.db.get_series_by_id(id).unwrap().unwrap();
+            let changes = update_one_series(&mut ctx, &mut series, true, dry_run)
+                .unwrap()
+                .unwrap();
+            let ids =
+                |episodes: &[EpisodeDetails]| episodes.iter().map(|ep| ep.id).collect::<Vec<_>>();
+            assert_eq!(ids(&changes.added_episodes), [finale.id]);
+            assert_eq!(ids(&changes.removed_episodes), [dropped.id]);
+            assert!(
+                changes
+                    .summary()
+                    .contains(" - Removed episodes:\n   - S01E99 ")
+            );
+        }
+
+        let history = ctx.db.get_series_history(id).unwrap();
+        let recorded: SeriesDetailsChanges =
+            serde_json::from_value(history[0].changes_json.clone()).unwrap();
+        assert_eq!(recorded.added_episodes.len(), 1);
+        assert_eq!(recorded.added_episodes[0].id, finale.id);
+
+        let episodes = ctx.db.get_episodes_of_season(id, 1).unwrap();
+        assert!(episodes.iter().all(|ep| ep.id != dropped.id));
+    }
+
+    #[test]
+    fn seasons_missed_before_are_backfilled() {
+        let mut old_details = testing::make_series_details(1, "Some Series");
+        old_details.number_of_seasons = 3;
+        old_details
+            .last_episode_to_air
+            .as_mut()
+            .unwrap()
+            .season_number = 3;
+        let new_details = old_details.clone();
+
+        assert_eq!(
+            seasons_to_refresh(&old_details, &new_details, &[1, 2, 3]),
+            3..=3
+        );
+        assert_eq!(
+            seasons_to_refresh(&old_details, &new_details, &[1, 3]),
+            2..=3
+        );
+        assert_eq!(seasons_to_refresh(&old_details, &new_details, &[]), 1..=3);
+    }
+
+    #[test]
+    fn dry_run_update_leaves_the_database_untouched() {
+        let mut ctx = testing::make_context();
+        let old_details = testing::make_outdated_series_details(&mut ctx, tmdb::SeriesId(108545));
+        let mut series = testing::insert_series(&mut ctx, old_details, &[]);
+
+        let changes = update_one_series(&mut ctx, &mut series, true, true).unwrap();
+        assert!(changes.is_some_and(|c| c.aired_episodes.len() == 3));
+
+        let stored = ctx.db.get_series_by_id(series.tmdb_id).unwrap().unwrap();
+        assert_eq!(stored.details.number_of_episodes, 5);
+        assert!(
+            ctx.db
+                .get_episodes_of_series(series.tmdb_id)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn failed_update_leaves_the_series_untouched() {
+        let mut ctx = testing::make_context();
+        let old_details = testing::make_outdated_series_details(&mut ctx, tmdb::SeriesId(108545));
+        let mut series = testing::insert_series(&mut ctx, old_details, &[]);
+        // the last step of an update
+        testing::inject_failure(&mut ctx, "INSERT", "series_history");
+
+        let err = update_one_series(&mut ctx, &mut series, true, false).unwrap_err();
+        assert!(format!("{err:#}").contains("injected failure"));
+        assert_eq!(series.details.number_of_episodes, 5);
+
+        let stored = ctx.db.get_series_by_id(series.tmdb_id).unwrap().unwrap();
+        assert_eq!(stored.details.number_of_episodes, 5);
+        assert_eq!(stored.update_timestamp, series.update_timestamp);
+        assert!(stored.external_ids.imdb_id.is_none());
+        assert_eq!(testing::count_rows(&mut ctx, "seasons"), 0);
+        assert_eq!(testing::count_rows(&mut ctx, "episodes"), 0);
+    }
+
+    #[test]
+    fn series_gone_from_tmdb_is_orphaned_and_reported_once() {
+        let mut ctx = testing::make_context();
+        let mut series = testing::insert_series(
+            &mut ctx,
+            testing::make_series_details(1, "Deleted Series"),
+            &[],
+        );
+
+        let changes = update_one_series(&mut ctx, &mut series, true, false)
+            .unwrap()
+            .unwrap();
+        assert!(changes.orphaned);
+        assert!(changes.summary().contains("Removed from TMDB"));
+
+        let stored = ctx.db.get_series_by_id(series.tmdb_id).unwrap().unwrap();
+        assert_eq!(stored.orphaned_timestamp, series.orphaned_timestamp);
+        assert!(stored.orphaned_timestamp.is_some());
+
+        // orphaned series are skipped from now on, unless forced, and even then they are not reported again
+        assert!(
+            update_all_series(&mut ctx, false, false)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(update_all_series(&mut ctx, true, false).unwrap().is_empty());
+    }
+}
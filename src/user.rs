@@ -0,0 +1,50 @@
+use anyhow::bail;
+
+use crate::db;
+
+use super::AppContext;
+
+/// Resolves the `--user` command line option (ID, name or e-mail address).
+/// If not given, falls back to the only user in the database, if there's exactly one.
+pub fn resolve_user(ctx: &mut AppContext, user: Option<&str>) -> anyhow::Result<db::User> {
+    if let Some(user_key) = user {
+        let Some(user) = ctx.db.find_user(user_key)? else {
+            bail!("No such user: {user_key}")
+        };
+        return Ok(user);
+    }
+
+    let mut users = ctx.db.get_all_users()?;
+    match users.len() {
+        0 => bail!("There are no users yet, add one with `user add`"),
+        1 => Ok(users.pop().unwrap()),
+        n => bail!("There are {n} users, specify which one to use with --user"),
+    }
+}
+
+pub fn add_user(ctx: &mut AppContext, name: &str, email: &str) -> anyhow::Result<db::User> {
+    if let Err(err) = email.parse::<lettre::Address>() {
+        bail!("Invalid e-mail address {email:?}: {err}");
+    }
+
+    let user = ctx.db.insert_user(name, email)?;
+    log::info!("Added user {user}");
+    Ok(user)
+}
+
+pub fn list_users(ctx: &mut AppContext) -> anyhow::Result<()> {
+    let users = ctx.db.get_all_users()?;
+    for user in users.iter() {
+        let tracked_count = ctx.db.get_all_series_tracked_by_user(user.id)?.len();
+        println!("{user} | tracking {tracked_count} series");
+    }
+    println!("{} users", users.len());
+    Ok(())
+}
+
+pub fn remove_user(ctx: &mut AppContext, user_key: &str) -> anyhow::Result<()> {
+    let user = resolve_user(ctx, Some(user_key))?;
+    ctx.db.delete_user(&user)?;
+    log::info!("Removed user {user} and all their subscriptions");
+    Ok(())
+}
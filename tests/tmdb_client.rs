@@ -0,0 +1,201 @@
+//! Tests for the retry and rate limiting behaviour of the TMDB client, against a local mock server.
+
+use std::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use tvtrack::{
+    config::TMDBConfig,
+    tmdb::{self, Api, SeriesId},
+};
+
+struct MockResponse {
+    status: u16,
+    retry_after: Option<&'static str>,
+    delay: Duration,
+}
+
+impl MockResponse {
+    fn status(status: u16) -> MockResponse {
+        MockResponse {
+            status,
+            retry_after: None,
+            delay: Duration::ZERO,
+        }
+    }
+}
+
+const SEASON_JSON: &str = r#"{ "id": 160939, "season_number": 1, "name": "Season 1", "air_date": "2024-03-21", "episodes": [] }"#;
+
+/// Starts a local HTTP server which responds to consecutive requests with the given responses;
+/// successful ones have `SEASON_JSON` as the body.
+/// Returns the root URL of the server and a channel receiving the time of each request.
+fn start_mock_server(responses: Vec<MockResponse>) -> (String, mpsc::Receiver<Instant>) {
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/3/", server.server_addr().to_ip().unwrap());
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        for mock in responses {
+            let request = server.recv().unwrap();
+            sender.send(Instant::now()).unwrap();
+            std::thread::sleep(mock.delay);
+
+            let body = if mock.status == 200 { SEASON_JSON } else { "" };
+            let mut response = tiny_http::Response::from_string(body).with_status_code(mock.status);
+            if let Some(retry_after) = mock.retry_after {
+                response
+                    .add_header(tiny_http::Header::from_bytes("Retry-After", retry_after).unwrap());
+            }
+            // the client may have timed out and gone away already
+            let _ = request.respond(response);
+        }
+    });
+
+    (url, receiver)
+}
+
+fn make_config(api_base_url: String) -> TMDBConfig {
+    TMDBConfig {
+        api_key: "test-api-key".to_owned(),
+        api_access_token: "test-api-access-token".to_owned(),
+        api_base_url,
+        timeout_secs: 1,
+        max_attempts: 3,
+        retry_delay_ms: 10,
+        max_retry_after_secs: 2,
+        max_requests_per_second: 0,
+    }
+}
+
+fn make_client(api_base_url: String) -> tmdb::Client {
+    tmdb::Client::new(&make_config(api_base_url))
+}
+
+#[test]
+fn retries_server_errors_with_backoff() {
+    let (url, requests) = start_mock_server(vec![
+        MockResponse::status(500),
+        MockResponse::status(503),
+        MockResponse::status(200),
+    ]);
+    let mut client = make_client(url);
+
+    let season = client.get_season_details(SeriesId(108545), 1).unwrap();
+    assert_eq!(season.name, "Season 1");
+
+    let times: Vec<_> = requests.try_iter().collect();
+    assert_eq!(times.len(), 3);
+    // the second retry waits at least half of twice the retry delay
+    assert!(times[2] - times[1] >= Duration::from_millis(10));
+}
+
+#[test]
+fn gives_up_after_max_attempts() {
+    let (url, requests) = start_mock_server(vec![
+        MockResponse::status(502),
+        MockResponse::status(502),
+        MockResponse::status(502),
+        MockResponse::status(200),
+    ]);
+    let mut client = make_client(url);
+
+    let err = client.get_season_details(SeriesId(108545), 1).unwrap_err();
+    assert!(format!("{err:#}").contains("HTTP status 502"));
+    assert_eq!(requests.try_iter().count(), 3);
+}
+
+#[test]
+fn does_not_retry_client_errors() {
+    let (url, requests) =
+        start_mock_server(vec![MockResponse::status(404), MockResponse::status(200)]);
+    let mut client = make_client(url);
+
+    let err = client.get_series_details(SeriesId(1)).unwrap_err();
+    assert!(tmdb::is_not_found(&err));
+    requests.recv().unwrap();
+    assert!(requests.recv_timeout(Duration::from_millis(100)).is_err());
+}
+
+#[test]
+fn honors_retry_after_on_too_many_requests() {
+    let (url, requests) = start_mock_server(vec![
+        MockResponse {
+            retry_after: Some("1"),
+            ..MockResponse::status(429)
+        },
+        MockResponse::status(200),
+    ]);
+    let mut client = make_client(url);
+
+    client.get_season_details(SeriesId(108545), 1).unwrap();
+
+    let times: Vec<_> = requests.try_iter().collect();
+    assert_eq!(times.len(), 2);
+    assert!(times[1] - times[0] >= Duration::from_secs(1));
+}
+
+#[test]
+fn gives_up_if_asked_to_retry_after_too_long() {
+    let (url, requests) = start_mock_server(vec![
+        MockResponse {
+            retry_after: Some("3600"),
+            ..MockResponse::status(429)
+        },
+        MockResponse::status(200),
+    ]);
+    let mut client = make_client(url);
+
+    let start = Instant::now();
+    let err = client.get_season_details(SeriesId(108545), 1).unwrap_err();
+    assert!(format!("{err:#}").contains("longer than the maximum of 2s"));
+    assert!(start.elapsed() < Duration::from_secs(2));
+    requests.recv().unwrap();
+    assert!(requests.recv_timeout(Duration::from_millis(100)).is_err());
+}
+
+#[test]
+fn rejects_malformed_external_ids_without_a_request() {
+    let (url, requests) = start_mock_server(vec![MockResponse::status(200)]);
+    let mut client = make_client(url);
+
+    let err = client
+        .find_by_external_id(tmdb::ExternalSource::Imdb, "tt1/../../tv/1")
+        .unwrap_err();
+    assert!(format!("{err:#}").contains("Invalid imdb ID"));
+    assert!(requests.recv_timeout(Duration::from_millis(100)).is_err());
+}
+
+#[test]
+fn retries_timed_out_requests() {
+    let (url, requests) = start_mock_server(vec![
+        MockResponse {
+            delay: Duration::from_millis(1500),
+            ..MockResponse::status(200)
+        },
+        MockResponse::status(200),
+    ]);
+    let mut client = make_client(url);
+
+    client.get_season_details(SeriesId(108545), 1).unwrap();
+    assert_eq!(requests.try_iter().count(), 2);
+}
+
+#[test]
+fn rate_limits_requests() {
+    let (url, requests) = start_mock_server((0..3).map(|_| MockResponse::status(200)).collect());
+    let mut client = tmdb::Client::new(&TMDBConfig {
+        max_requests_per_second: 5,
+        ..make_config(url)
+    });
+
+    for _ in 0..3 {
+        client.get_season_details(SeriesId(108545), 1).unwrap();
+    }
+
+    let times: Vec<_> = requests.try_iter().collect();
+    assert_eq!(times.len(), 3);
+    // 200ms apart, give or take some scheduling noise
+    assert!(times[2] - times[0] >= Duration::from_millis(350));
+}